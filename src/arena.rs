@@ -0,0 +1,223 @@
+use bevy::prelude::*;
+
+use crate::{GameState, BOUNDS};
+
+pub struct ArenaPlugin;
+
+impl Plugin for ArenaPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Arena>().add_systems(
+            Update,
+            (spawn_wrap_ghosts, update_wrap_ghosts, sync_ghost_children)
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+}
+
+/// Shape of the play field
+#[derive(Resource, Default)]
+pub struct Arena {
+    /// Leaving one edge brings you back on the opposite one, like classic Asteroids
+    pub wrap: bool,
+}
+
+impl Arena {
+    pub fn label(&self) -> &'static str {
+        if self.wrap {
+            "Arena: Wrap Around"
+        } else {
+            "Arena: Walled"
+        }
+    }
+
+    /// Shortest offset from `from` to `to`, going across the seam when that is closer
+    pub fn delta(&self, from: Vec2, to: Vec2) -> Vec2 {
+        let delta = to - from;
+        if !self.wrap {
+            return delta;
+        }
+        delta - BOUNDS * (delta / BOUNDS).round()
+    }
+
+    pub fn distance(&self, a: Vec2, b: Vec2) -> f32 {
+        self.delta(a, b).length()
+    }
+
+    /// Brings a position that left the arena back in, either against the wall
+    /// or through to the other side
+    pub fn confine(&self, pos: Vec3) -> Vec3 {
+        let half = Vec3::from((BOUNDS / 2.0, 0.0));
+        if self.wrap {
+            let size = Vec3::from((BOUNDS, 1.0));
+            let wrapped = (pos + half).rem_euclid(size) - half;
+            wrapped.with_z(pos.z)
+        } else {
+            pos.min(half).max(-half)
+        }
+    }
+
+    /// Whether something at `pos` has left the arena for good
+    pub fn is_outside(&self, pos: Vec2, radius: f32) -> bool {
+        !self.wrap && pos.abs().cmpgt(BOUNDS / 2.0 + radius).any()
+    }
+}
+
+/// Drawn again on the far side of the arena while it straddles the seam
+#[derive(Component)]
+pub struct Wraps {
+    pub radius: f32,
+}
+
+/// Copy of a `Wraps` entity's visuals, offset across one or both seams
+#[derive(Component)]
+struct WrapGhost {
+    across_x: bool,
+    across_y: bool,
+}
+
+/// Carried by a ghost in place of one of the real entity's drawn children,
+/// such as a ship's shield bubble
+#[derive(Component)]
+struct GhostChild(Entity);
+
+fn spawn_wrap_ghosts(
+    mut commands: Commands,
+    added: Query<
+        (Entity, Option<&Sprite>, Option<&Mesh2d>, Option<&MeshMaterial2d<ColorMaterial>>),
+        Added<Wraps>,
+    >,
+) {
+    for (entity, sprite, mesh, material) in &added {
+        for (across_x, across_y) in [(true, false), (false, true), (true, true)] {
+            let mut ghost = commands.spawn((
+                WrapGhost { across_x, across_y },
+                Transform::default(),
+                Visibility::Hidden,
+                ChildOf(entity),
+            ));
+            if let Some(sprite) = sprite {
+                ghost.insert(sprite.clone());
+            }
+            if let (Some(mesh), Some(material)) = (mesh, material) {
+                ghost.insert((mesh.clone(), material.clone()));
+            }
+        }
+    }
+}
+
+fn update_wrap_ghosts(
+    arena: Res<Arena>,
+    parents: Query<
+        (&Transform, &Wraps, &Children, Option<&Sprite>, Option<&MeshMaterial2d<ColorMaterial>>),
+        Without<WrapGhost>,
+    >,
+    mut ghosts: Query<(
+        &WrapGhost,
+        &mut Transform,
+        &mut Visibility,
+        Option<&mut Sprite>,
+        Option<&mut MeshMaterial2d<ColorMaterial>>,
+    )>,
+) {
+    let half = BOUNDS / 2.0;
+    for (transform, wraps, children, sprite, material) in &parents {
+        let pos = transform.translation.truncate();
+        // Which way the copy sits, if the entity is hanging over an edge
+        let shift = Vec2::new(
+            if pos.x > half.x - wraps.radius {
+                -BOUNDS.x
+            } else if pos.x < -half.x + wraps.radius {
+                BOUNDS.x
+            } else {
+                0.0
+            },
+            if pos.y > half.y - wraps.radius {
+                -BOUNDS.y
+            } else if pos.y < -half.y + wraps.radius {
+                BOUNDS.y
+            } else {
+                0.0
+            },
+        );
+
+        for child in children.iter() {
+            let Ok((ghost, mut ghost_tf, mut visibility, ghost_sprite, ghost_material)) = ghosts.get_mut(child) else {
+                continue;
+            };
+            let offset = Vec2::new(
+                if ghost.across_x { shift.x } else { 0.0 },
+                if ghost.across_y { shift.y } else { 0.0 },
+            );
+            let needed = arena.wrap
+                && (!ghost.across_x || shift.x != 0.0)
+                && (!ghost.across_y || shift.y != 0.0);
+
+            *visibility = if needed { Visibility::Inherited } else { Visibility::Hidden };
+            // Children inherit the parent's rotation, so undo it for a world space offset
+            ghost_tf.translation = transform.rotation.inverse() * offset.extend(0.0);
+
+            // Keep up with blinking, tints and swapped art
+            if let (Some(sprite), Some(mut ghost_sprite)) = (sprite, ghost_sprite)
+                && (ghost_sprite.color != sprite.color || ghost_sprite.image != sprite.image)
+            {
+                *ghost_sprite = sprite.clone();
+            }
+            if let (Some(material), Some(mut ghost_material)) = (material, ghost_material)
+                && ghost_material.0 != material.0
+            {
+                ghost_material.0 = material.0.clone();
+            }
+        }
+    }
+}
+
+/// Gives every ghost its own copy of the real entity's drawn children, and
+/// drops copies whose original has gone. Copies share the original's mesh and
+/// material, so a flickering shield flickers on both sides of the seam.
+fn sync_ghost_children(
+    mut commands: Commands,
+    parents: Query<&Children, With<Wraps>>,
+    ghosts: Query<Option<&Children>, With<WrapGhost>>,
+    originals: Query<
+        (&Transform, &Mesh2d, &MeshMaterial2d<ColorMaterial>),
+        (Without<WrapGhost>, Without<GhostChild>),
+    >,
+    mut copies: Query<(&GhostChild, &mut Transform)>,
+) {
+    for children in &parents {
+        let drawn: Vec<Entity> = children.iter().filter(|&child| originals.contains(child)).collect();
+
+        for ghost in children.iter() {
+            let Ok(ghost_children) = ghosts.get(ghost) else {
+                continue;
+            };
+            let mut copied = Vec::new();
+            for copy in ghost_children.into_iter().flat_map(|children| children.iter()) {
+                let Ok((GhostChild(original), mut copy_tf)) = copies.get_mut(copy) else {
+                    continue;
+                };
+                match originals.get(*original) {
+                    Ok((transform, _, _)) if drawn.contains(original) => {
+                        copy_tf.set_if_neq(*transform);
+                        copied.push(*original);
+                    }
+                    _ => commands.entity(copy).despawn(),
+                }
+            }
+
+            for &original in drawn.iter().filter(|original| !copied.contains(original)) {
+                let Ok((transform, mesh, material)) = originals.get(original) else {
+                    continue;
+                };
+                commands.spawn((
+                    GhostChild(original),
+                    mesh.clone(),
+                    material.clone(),
+                    *transform,
+                    ChildOf(ghost),
+                ));
+            }
+        }
+    }
+}
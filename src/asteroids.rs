@@ -0,0 +1,452 @@
+use std::f32::consts::TAU;
+
+use bevy::{
+    asset::RenderAssetUsages,
+    ecs::system::SystemParam,
+    mesh::{Indices, PrimitiveTopology},
+    prelude::*,
+};
+use rand::Rng;
+
+use crate::{arena::Wraps, damage::ShipDestroyed, physics::sweep_entry, tuning::GameTuning, Asteroid, GameEntity, GameState};
+
+/// Corners on a generated asteroid outline
+const MIN_CORNERS: usize = 9;
+const MAX_CORNERS: usize = 14;
+/// How far a corner can sit in from, or out past, the nominal radius
+const JAGGEDNESS: (f32, f32) = (0.7, 1.1);
+/// Radians per second either way
+const MAX_SPIN: f32 = 0.8;
+
+/// Sprite variants per size tier, `assets/asteroids/<tier>_<n>.png`
+const ART_VARIANTS: usize = 3;
+/// How much of its texture a rock sprite fills, so the drawn edge lines up with the collider
+const ART_FILL: f32 = 0.9;
+/// Corners on the outline used to hit test sprite asteroids
+const ROUND_CORNERS: usize = 16;
+/// Crumble sprite sheet, a single row of square frames
+const CRUMBLE_PATH: &str = "asteroids/crumble.png";
+const CRUMBLE_FRAMES: u32 = 8;
+const CRUMBLE_FRAME_SIZE: u32 = 64;
+/// Seconds per crumble frame
+const CRUMBLE_FRAME_TIME: f32 = 0.06;
+/// Seconds a chip of debris lasts
+const DEBRIS_LIFETIME: f32 = 0.8;
+const DEBRIS_SPEED: (f32, f32) = (40.0, 160.0);
+
+pub struct AsteroidsPlugin;
+
+impl Plugin for AsteroidsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<AsteroidDestroyed>()
+            .add_systems(Startup, load_asteroid_art)
+            .add_systems(
+                Update,
+                (spawn_destruction_effects, animate_crumble, move_debris)
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+/// Sent by `projectile_asteroid_collision` when a shot breaks an asteroid, for the crumble and debris
+#[derive(Message)]
+pub struct AsteroidDestroyed {
+    pub position: Vec3,
+    pub radius: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AsteroidSize {
+    Small,
+    Medium,
+    Large,
+}
+
+impl AsteroidSize {
+    const ALL: [AsteroidSize; 3] = [AsteroidSize::Small, AsteroidSize::Medium, AsteroidSize::Large];
+
+    pub fn random(rng: &mut impl Rng) -> Self {
+        Self::ALL[rng.random_range(0..Self::ALL.len())]
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            AsteroidSize::Small => "small",
+            AsteroidSize::Medium => "medium",
+            AsteroidSize::Large => "large",
+        }
+    }
+
+    pub fn radius(self, tuning: &GameTuning) -> f32 {
+        let (small, medium, large) = tuning.asteroid_radii;
+        match self {
+            AsteroidSize::Small => small,
+            AsteroidSize::Medium => medium,
+            AsteroidSize::Large => large,
+        }
+    }
+
+    /// What it breaks into when shot, small ones crumble to nothing
+    pub fn smaller(self) -> Option<Self> {
+        match self {
+            AsteroidSize::Small => None,
+            AsteroidSize::Medium => Some(AsteroidSize::Small),
+            AsteroidSize::Large => Some(AsteroidSize::Medium),
+        }
+    }
+}
+
+/// Jagged outline of an asteroid in its own space, used for both drawing and hits
+#[derive(Component)]
+pub struct AsteroidShape {
+    /// Counter clockwise around the centre
+    points: Vec<Vec2>,
+}
+
+impl AsteroidShape {
+    pub fn random(radius: f32, rng: &mut impl Rng) -> Self {
+        let corners = rng.random_range(MIN_CORNERS..=MAX_CORNERS);
+        let step = TAU / corners as f32;
+        let points = (0..corners)
+            .map(|i| {
+                // Wobble the angle a little too so the corners aren't evenly spaced
+                let angle = (i as f32 + rng.random_range(-0.3..0.3)) * step;
+                Vec2::from_angle(angle) * radius * rng.random_range(JAGGEDNESS.0..JAGGEDNESS.1)
+            })
+            .collect();
+        Self { points }
+    }
+
+    /// Even outline for sprite asteroids, whose art is close to round
+    pub fn round(radius: f32) -> Self {
+        let step = TAU / ROUND_CORNERS as f32;
+        let points = (0..ROUND_CORNERS)
+            .map(|i| Vec2::from_angle(i as f32 * step) * radius)
+            .collect();
+        Self { points }
+    }
+
+    /// Furthest corner from the centre
+    pub fn max_radius(&self) -> f32 {
+        self.points.iter().map(|p| p.length()).fold(0.0, f32::max)
+    }
+
+    /// Average corner distance, close enough to the outline for rock on rock bounces
+    pub fn mean_radius(&self) -> f32 {
+        self.points.iter().map(|p| p.length()).sum::<f32>() / self.points.len() as f32
+    }
+
+    /// Triangle fan out from the centre
+    pub fn mesh(&self) -> Mesh {
+        let max = self.max_radius();
+        let mut positions = vec![[0.0, 0.0, 0.0]];
+        let mut uvs = vec![[0.5, 0.5]];
+        for p in &self.points {
+            positions.push([p.x, p.y, 0.0]);
+            uvs.push([0.5 + p.x / (2.0 * max), 0.5 - p.y / (2.0 * max)]);
+        }
+        let corners = self.points.len() as u32;
+        let indices = (0..corners)
+            .flat_map(|i| [0, i + 1, (i + 1) % corners + 1])
+            .collect();
+
+        Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+            .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, 1.0]; positions.len()])
+            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+            .with_inserted_indices(Indices::U32(indices))
+    }
+
+    /// Whether a circle touches the outline. `offset` runs from the asteroid's
+    /// centre to the circle's, in world space.
+    pub fn overlaps_circle(&self, rotation: Quat, offset: Vec2, radius: f32) -> bool {
+        self.contact(rotation, offset, radius).is_some()
+    }
+
+    /// World space direction to push a touching circle out along, and how far
+    /// it has sunk in
+    pub fn contact(&self, rotation: Quat, offset: Vec2, radius: f32) -> Option<(Vec2, f32)> {
+        if offset.length() > self.max_radius() + radius {
+            return None;
+        }
+        let local = (rotation.inverse() * offset.extend(0.0)).truncate();
+
+        let mut inside = false;
+        let mut closest = self.points[0];
+        for (i, a) in self.points.iter().enumerate() {
+            let b = self.points[(i + 1) % self.points.len()];
+            // Crossing test for the centre being inside
+            if (a.y > local.y) != (b.y > local.y)
+                && local.x < a.x + (local.y - a.y) / (b.y - a.y) * (b.x - a.x)
+            {
+                inside = !inside;
+            }
+            // Nearest point on the outline
+            let edge = b - *a;
+            let t = ((local - *a).dot(edge) / edge.length_squared()).clamp(0.0, 1.0);
+            let point = *a + edge * t;
+            if point.distance_squared(local) < closest.distance_squared(local) {
+                closest = point;
+            }
+        }
+
+        let gap = closest.distance(local);
+        let (normal, depth) = if inside {
+            ((closest - local).normalize_or(local.normalize_or(Vec2::X)), radius + gap)
+        } else if gap < radius {
+            ((local - closest).normalize_or(Vec2::X), radius - gap)
+        } else {
+            return None;
+        };
+        Some(((rotation * normal.extend(0.0)).truncate(), depth))
+    }
+
+    /// How far along `sweep`, as a fraction, a circle setting off from `start`
+    /// first touches the outline. `start` is relative to the asteroid's centre,
+    /// both in world space.
+    pub fn sweep_circle(&self, rotation: Quat, start: Vec2, sweep: Vec2, radius: f32) -> Option<f32> {
+        if start.length() > self.max_radius() + radius + sweep.length() {
+            return None;
+        }
+        if self.overlaps_circle(rotation, start, radius) {
+            return Some(0.0);
+        }
+        let inverse = rotation.inverse();
+        let start = (inverse * start.extend(0.0)).truncate();
+        let sweep = (inverse * sweep.extend(0.0)).truncate();
+
+        // Touching means the centre reaching an edge pushed out by the radius,
+        // or coming within the radius of a corner
+        let mut first: Option<f32> = None;
+        for (i, a) in self.points.iter().enumerate() {
+            let edge = self.points[(i + 1) % self.points.len()] - *a;
+            let outward = Vec2::new(edge.y, -edge.x).normalize_or_zero() * radius;
+            let hits = [
+                segment_crossing(start, sweep, *a + outward, edge),
+                sweep_entry(*a - start, sweep, radius),
+            ];
+            for t in hits.into_iter().flatten() {
+                first = Some(first.map_or(t, |first| first.min(t)));
+            }
+        }
+        first
+    }
+}
+
+/// Fraction along `sweep` from `start` at which it crosses `edge` from `corner`
+fn segment_crossing(start: Vec2, sweep: Vec2, corner: Vec2, edge: Vec2) -> Option<f32> {
+    let denominator = sweep.perp_dot(edge);
+    if denominator.abs() < f32::EPSILON {
+        return None;
+    }
+    let to_corner = corner - start;
+    let t = to_corner.perp_dot(edge) / denominator;
+    let u = to_corner.perp_dot(sweep) / denominator;
+    ((0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u)).then_some(t)
+}
+
+/// Rock sprites and the crumble sheet. Any that fail to load leave
+/// asteroids drawn as plain meshes.
+#[derive(Resource)]
+struct AsteroidArt {
+    /// Indexed by `AsteroidSize`
+    rocks: [Vec<Handle<Image>>; 3],
+    crumble: Handle<Image>,
+    crumble_layout: Handle<TextureAtlasLayout>,
+    debris_mesh: Handle<Mesh>,
+    debris_material: Handle<ColorMaterial>,
+}
+
+fn load_asteroid_art(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let rocks = AsteroidSize::ALL.map(|size| {
+        (0..ART_VARIANTS)
+            .map(|n| asset_server.load(format!("asteroids/{}_{n}.png", size.name())))
+            .collect()
+    });
+    commands.insert_resource(AsteroidArt {
+        rocks,
+        crumble: asset_server.load(CRUMBLE_PATH),
+        crumble_layout: layouts.add(TextureAtlasLayout::from_grid(
+            UVec2::splat(CRUMBLE_FRAME_SIZE),
+            CRUMBLE_FRAMES,
+            1,
+            None,
+            None,
+        )),
+        debris_mesh: meshes.add(Rectangle::new(4.0, 3.0)),
+        debris_material: materials.add(Color::srgb(0.6, 0.58, 0.55)),
+    });
+}
+
+/// Everything needed to put a new asteroid in the field
+#[derive(SystemParam)]
+pub struct AsteroidFactory<'w> {
+    pub meshes: ResMut<'w, Assets<Mesh>>,
+    pub materials: ResMut<'w, Assets<ColorMaterial>>,
+    images: Res<'w, Assets<Image>>,
+    art: Res<'w, AsteroidArt>,
+    tuning: Res<'w, GameTuning>,
+}
+
+impl AsteroidFactory<'_> {
+    pub fn spawn(
+        &mut self,
+        commands: &mut Commands,
+        size: AsteroidSize,
+        position: Vec3,
+        velocity: Vec2,
+    ) -> Entity {
+        let mut rng = rand::rng();
+        let nominal = size.radius(&self.tuning);
+
+        // Only pick from the variants that actually made it off disk
+        let loaded: Vec<&Handle<Image>> = self.art.rocks[size.index()]
+            .iter()
+            .filter(|handle| self.images.contains(*handle))
+            .collect();
+
+        let image = loaded.get(rng.random_range(0..loaded.len().max(1))).copied();
+        let shape = match image {
+            Some(_) => AsteroidShape::round(nominal),
+            None => AsteroidShape::random(nominal, &mut rng),
+        };
+
+        let mut asteroid = commands.spawn((
+            Transform::from_translation(position)
+                .with_rotation(Quat::from_rotation_z(rng.random_range(0.0..TAU))),
+            Asteroid {
+                velocity,
+                radius: shape.mean_radius(),
+                size,
+                spin: rng.random_range(-MAX_SPIN..MAX_SPIN),
+            },
+            Wraps { radius: shape.max_radius() },
+            GameEntity,
+        ));
+        match image {
+            Some(image) => asteroid.insert(Sprite {
+                image: image.clone(),
+                custom_size: Some(Vec2::splat(nominal * 2.0 / ART_FILL)),
+                ..default()
+            }),
+            None => asteroid.insert((
+                Mesh2d(self.meshes.add(shape.mesh())),
+                MeshMaterial2d(self.materials.add(Color::srgb(0.8, 0.8, 0.8))),
+            )),
+        };
+        asteroid.insert(shape).id()
+    }
+}
+
+/// Crumble animation, removed after its last frame
+#[derive(Component)]
+struct Crumble {
+    timer: Timer,
+}
+
+#[derive(Component)]
+struct Debris {
+    velocity: Vec2,
+    spin: f32,
+    lifetime: Timer,
+}
+
+fn spawn_destruction_effects(
+    mut commands: Commands,
+    mut destroyed: MessageReader<AsteroidDestroyed>,
+    mut ships: MessageReader<ShipDestroyed>,
+    art: Res<AsteroidArt>,
+    images: Res<Assets<Image>>,
+) {
+    let mut rng = rand::rng();
+    // Ships go up in the same burst of dust and chips
+    let bursts = destroyed
+        .read()
+        .map(|event| (event.position, event.radius))
+        .chain(ships.read().map(|event| (event.position, event.radius)));
+    for (position, radius) in bursts {
+        if images.contains(&art.crumble) {
+            commands.spawn((
+                Sprite {
+                    custom_size: Some(Vec2::splat(radius * 2.5)),
+                    ..Sprite::from_atlas_image(
+                        art.crumble.clone(),
+                        TextureAtlas { layout: art.crumble_layout.clone(), index: 0 },
+                    )
+                },
+                Transform::from_translation(position.with_z(1.0)),
+                Crumble {
+                    timer: Timer::from_seconds(CRUMBLE_FRAME_TIME, TimerMode::Repeating),
+                },
+                GameEntity,
+            ));
+        }
+
+        // Bigger rocks throw off more chips
+        let chips = (radius / 4.0) as usize + 4;
+        for _ in 0..chips {
+            let direction = Vec2::from_angle(rng.random_range(0.0..TAU));
+            let offset = direction * rng.random_range(0.0..radius);
+            commands.spawn((
+                Mesh2d(art.debris_mesh.clone()),
+                MeshMaterial2d(art.debris_material.clone()),
+                Transform::from_translation(position + offset.extend(0.0))
+                    .with_rotation(Quat::from_rotation_z(rng.random_range(0.0..TAU))),
+                Debris {
+                    velocity: direction * rng.random_range(DEBRIS_SPEED.0..DEBRIS_SPEED.1),
+                    spin: rng.random_range(-8.0..8.0),
+                    lifetime: Timer::from_seconds(
+                        DEBRIS_LIFETIME * rng.random_range(0.5..1.0),
+                        TimerMode::Once,
+                    ),
+                },
+                GameEntity,
+            ));
+        }
+    }
+}
+
+fn animate_crumble(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Crumble, &mut Sprite)>,
+) {
+    for (entity, mut crumble, mut sprite) in &mut query {
+        crumble.timer.tick(time.delta());
+        let Some(atlas) = sprite.texture_atlas.as_mut() else {
+            continue;
+        };
+        atlas.index += crumble.timer.times_finished_this_tick() as usize;
+        if atlas.index >= CRUMBLE_FRAMES as usize {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn move_debris(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Debris, &mut Transform)>,
+) {
+    for (entity, mut debris, mut transform) in &mut query {
+        debris.lifetime.tick(time.delta());
+        if debris.lifetime.is_finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        transform.translation += (debris.velocity * time.delta_secs()).extend(0.0);
+        transform.rotate_z(debris.spin * time.delta_secs());
+        // Shrink away rather than fade, so every chip can share one material
+        transform.scale = Vec3::splat(debris.lifetime.fraction_remaining());
+    }
+}
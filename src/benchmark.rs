@@ -0,0 +1,83 @@
+use std::f32::consts::TAU;
+
+use bevy::{
+    diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    prelude::*,
+};
+use rand::Rng;
+
+use crate::{
+    asteroids::{AsteroidFactory, AsteroidSize},
+    physics::{BroadPhase, PhysicsSet},
+    Asteroid, BOUNDS,
+};
+
+const DEFAULT_ASTEROIDS: usize = 1500;
+const BENCHMARK_SPEED: (f32, f32) = (20.0, 80.0);
+
+/// Stress scene for the collision broad phase. Does nothing unless the game is
+/// started with `cargo run --release -- --benchmark [asteroids] [--brute-force]`,
+/// then keeps the field topped up with small asteroids during a match and logs
+/// frame times. Running with and without `--brute-force` shows what the grid saves.
+pub struct BenchmarkPlugin;
+
+impl Plugin for BenchmarkPlugin {
+    fn build(&self, app: &mut App) {
+        let Some(benchmark) = Benchmark::from_args() else {
+            return;
+        };
+        info!(
+            "Benchmark: {} asteroids, {}",
+            benchmark.asteroids,
+            if benchmark.brute_force { "checking every pair" } else { "broad phase grid" }
+        );
+
+        let mut grid = BroadPhase::default();
+        grid.brute_force = benchmark.brute_force;
+        app.add_plugins((FrameTimeDiagnosticsPlugin::default(), LogDiagnosticsPlugin::default()))
+            .insert_resource(grid)
+            .insert_resource(benchmark)
+            .add_systems(FixedUpdate, top_up_asteroids.in_set(PhysicsSet::Input));
+    }
+}
+
+#[derive(Resource)]
+struct Benchmark {
+    asteroids: usize,
+    brute_force: bool,
+}
+
+impl Benchmark {
+    fn from_args() -> Option<Self> {
+        let args: Vec<String> = std::env::args().collect();
+        let flag = args.iter().position(|arg| arg == "--benchmark")?;
+        Some(Self {
+            asteroids: args
+                .get(flag + 1)
+                .and_then(|count| count.parse().ok())
+                .unwrap_or(DEFAULT_ASTEROIDS),
+            brute_force: args.iter().any(|arg| arg == "--brute-force"),
+        })
+    }
+}
+
+/// Replaces whatever drifted off or got shot, anywhere in the arena
+fn top_up_asteroids(
+    mut commands: Commands,
+    mut factory: AsteroidFactory,
+    benchmark: Res<Benchmark>,
+    asteroids: Query<(), With<Asteroid>>,
+) {
+    let mut rng = rand::rng();
+    let half = BOUNDS / 2.0;
+    for _ in asteroids.iter().count()..benchmark.asteroids {
+        let position = Vec3::new(
+            rng.random_range(-half.x..half.x),
+            rng.random_range(-half.y..half.y),
+            0.0,
+        );
+        let velocity = Vec2::from_angle(rng.random_range(0.0..TAU))
+            * rng.random_range(BENCHMARK_SPEED.0..BENCHMARK_SPEED.1);
+        factory.spawn(&mut commands, AsteroidSize::Small, position, velocity);
+    }
+}
@@ -0,0 +1,661 @@
+use std::{fs, path::PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{save_ron, spawn_menu_button, GameState, MenuAction, PlayerId};
+
+/// Keys the pause, join and results screens and the round reset listen for,
+/// whatever is bound, so they can't be given to an action
+const RESERVED_KEYS: [KeyCode; 4] = [KeyCode::Escape, KeyCode::Enter, KeyCode::Backspace, KeyCode::KeyR];
+/// Pad buttons that pause and leave the results screen
+const RESERVED_BUTTONS: [GamepadButton; 2] = [GamepadButton::Start, GamepadButton::Select];
+
+pub struct BindingsPlugin;
+
+impl Plugin for BindingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ControlBindings::load())
+            .init_resource::<Rebinding>()
+            .add_systems(OnEnter(GameState::Controls), spawn_controls_menu)
+            .add_systems(OnExit(GameState::Controls), stop_listening)
+            .add_systems(
+                Update,
+                (
+                    controls_buttons,
+                    listen_for_binding,
+                    update_binding_labels
+                        .run_if(resource_changed::<ControlBindings>.or(resource_changed::<Rebinding>)),
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Controls)),
+            );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Action {
+    RotateLeft,
+    RotateRight,
+    Thrust,
+    Fire,
+    Warp,
+    Shield,
+    Reload,
+}
+
+impl Action {
+    const ALL: [Action; 7] = [
+        Action::RotateLeft,
+        Action::RotateRight,
+        Action::Thrust,
+        Action::Fire,
+        Action::Warp,
+        Action::Shield,
+        Action::Reload,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Action::RotateLeft => "Rotate Left",
+            Action::RotateRight => "Rotate Right",
+            Action::Thrust => "Thrust",
+            Action::Fire => "Fire",
+            Action::Warp => "Warp",
+            Action::Shield => "Shield",
+            Action::Reload => "Reload",
+        }
+    }
+}
+
+/// One action can be triggered from the keyboard and from the player's own pad
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct ActionBinding {
+    pub key: Option<KeyCode>,
+    pub button: Option<GamepadButton>,
+}
+
+impl ActionBinding {
+    fn new(key: KeyCode, button: GamepadButton) -> Self {
+        Self {
+            key: Some(key),
+            button: Some(button),
+        }
+    }
+
+    fn input(&self, device: Device) -> Option<BoundInput> {
+        match device {
+            Device::Keyboard => self.key.map(BoundInput::Key),
+            Device::Gamepad => self.button.map(BoundInput::Button),
+        }
+    }
+
+    fn set(&mut self, device: Device, input: Option<BoundInput>) {
+        match (device, input) {
+            (Device::Keyboard, Some(BoundInput::Key(key))) => self.key = Some(key),
+            (Device::Keyboard, None) => self.key = None,
+            (Device::Gamepad, Some(BoundInput::Button(button))) => self.button = Some(button),
+            (Device::Gamepad, None) => self.button = None,
+            _ => {}
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SteeringMode {
+    /// Stick left/right turns the ship like the D-pad
+    Tank,
+    /// The ship turns to face wherever the stick points
+    PointToFace,
+}
+
+/// How the left stick and right trigger feel for one player
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct AnalogSettings {
+    pub steering: SteeringMode,
+    pub stick_dead_zone: f32,
+    pub trigger_dead_zone: f32,
+    /// Exponent applied after the dead zone, 1.0 is linear and higher is softer near the centre
+    pub stick_curve: f32,
+    pub trigger_curve: f32,
+}
+
+impl Default for AnalogSettings {
+    fn default() -> Self {
+        Self {
+            steering: SteeringMode::Tank,
+            stick_dead_zone: 0.2,
+            trigger_dead_zone: 0.1,
+            stick_curve: 1.5,
+            trigger_curve: 1.0,
+        }
+    }
+}
+
+impl AnalogSettings {
+    /// Largest dead zone allowed, so there is always some travel left to rescale
+    const MAX_DEAD_ZONE: f32 = 0.95;
+
+    /// Rescales `value` so the dead zone edge reads as zero, then applies the curve
+    pub fn shape(value: f32, dead_zone: f32, curve: f32) -> f32 {
+        let dead_zone = dead_zone.clamp(0.0, Self::MAX_DEAD_ZONE);
+        let live = ((value.abs() - dead_zone) / (1.0 - dead_zone)).clamp(0.0, 1.0);
+        live.powf(curve) * value.signum()
+    }
+
+    /// Pulls hand edited values back into a range `shape` can work with
+    fn sanitize(&mut self) {
+        let defaults = Self::default();
+        let dead_zone = |value: f32, default: f32| {
+            if value.is_finite() { value.clamp(0.0, Self::MAX_DEAD_ZONE) } else { default }
+        };
+        let curve = |value: f32, default: f32| {
+            if value.is_finite() && value > 0.0 { value } else { default }
+        };
+        self.stick_dead_zone = dead_zone(self.stick_dead_zone, defaults.stick_dead_zone);
+        self.trigger_dead_zone = dead_zone(self.trigger_dead_zone, defaults.trigger_dead_zone);
+        self.stick_curve = curve(self.stick_curve, defaults.stick_curve);
+        self.trigger_curve = curve(self.trigger_curve, defaults.trigger_curve);
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct PlayerBindings {
+    pub rotate_left: ActionBinding,
+    pub rotate_right: ActionBinding,
+    pub thrust: ActionBinding,
+    pub fire: ActionBinding,
+    pub warp: ActionBinding,
+    pub shield: ActionBinding,
+    pub reload: ActionBinding,
+    pub analog: AnalogSettings,
+}
+
+impl PlayerBindings {
+    fn red() -> Self {
+        Self {
+            rotate_left: ActionBinding::new(KeyCode::KeyA, GamepadButton::DPadLeft),
+            rotate_right: ActionBinding::new(KeyCode::KeyD, GamepadButton::DPadRight),
+            thrust: ActionBinding::new(KeyCode::KeyW, GamepadButton::DPadUp),
+            fire: ActionBinding::new(KeyCode::Space, GamepadButton::West),
+            warp: ActionBinding::new(KeyCode::KeyS, GamepadButton::South),
+            shield: ActionBinding::new(KeyCode::KeyQ, GamepadButton::North),
+            reload: ActionBinding::new(KeyCode::KeyE, GamepadButton::East),
+            analog: AnalogSettings::default(),
+        }
+    }
+
+    fn blue() -> Self {
+        Self {
+            rotate_left: ActionBinding::new(KeyCode::ArrowLeft, GamepadButton::DPadLeft),
+            rotate_right: ActionBinding::new(KeyCode::ArrowRight, GamepadButton::DPadRight),
+            thrust: ActionBinding::new(KeyCode::ArrowUp, GamepadButton::DPadUp),
+            fire: ActionBinding::new(KeyCode::ControlRight, GamepadButton::West),
+            warp: ActionBinding::new(KeyCode::ArrowDown, GamepadButton::South),
+            shield: ActionBinding::new(KeyCode::ShiftRight, GamepadButton::North),
+            reload: ActionBinding::new(KeyCode::Slash, GamepadButton::East),
+            analog: AnalogSettings::default(),
+        }
+    }
+
+    fn get(&self, action: Action) -> &ActionBinding {
+        match action {
+            Action::RotateLeft => &self.rotate_left,
+            Action::RotateRight => &self.rotate_right,
+            Action::Thrust => &self.thrust,
+            Action::Fire => &self.fire,
+            Action::Warp => &self.warp,
+            Action::Shield => &self.shield,
+            Action::Reload => &self.reload,
+        }
+    }
+
+    /// Puts back the default for any action a hand edited file gave a reserved input
+    fn unbind_reserved(&mut self, defaults: &PlayerBindings) {
+        for action in Action::ALL {
+            let default = defaults.get(action);
+            let binding = self.get_mut(action);
+            if binding.key.is_some_and(|key| RESERVED_KEYS.contains(&key)) {
+                warn!("{} can't use the reserved key {:?}, using the default", action.label(), binding.key);
+                binding.key = default.key;
+            }
+            if binding.button.is_some_and(|button| RESERVED_BUTTONS.contains(&button)) {
+                warn!("{} can't use the reserved button {:?}, using the default", action.label(), binding.button);
+                binding.button = default.button;
+            }
+        }
+    }
+
+    fn get_mut(&mut self, action: Action) -> &mut ActionBinding {
+        match action {
+            Action::RotateLeft => &mut self.rotate_left,
+            Action::RotateRight => &mut self.rotate_right,
+            Action::Thrust => &mut self.thrust,
+            Action::Fire => &mut self.fire,
+            Action::Warp => &mut self.warp,
+            Action::Shield => &mut self.shield,
+            Action::Reload => &mut self.reload,
+        }
+    }
+}
+
+impl Default for PlayerBindings {
+    fn default() -> Self {
+        Self::red()
+    }
+}
+
+/// Both players' controls, stored in `bindings.ron` in the user config directory
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ControlBindings {
+    pub players: [PlayerBindings; 2],
+}
+
+impl Default for ControlBindings {
+    fn default() -> Self {
+        Self {
+            players: [PlayerBindings::red(), PlayerBindings::blue()],
+        }
+    }
+}
+
+impl ControlBindings {
+    pub fn player(&self, id: PlayerId) -> &PlayerBindings {
+        &self.players[id.index()]
+    }
+
+    fn config_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("kuiper_belt").join("bindings.ron"))
+    }
+
+    fn load() -> Self {
+        let Some(path) = Self::config_path() else {
+            return Self::default();
+        };
+        let Ok(contents) = fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        match ron::from_str::<Self>(&contents) {
+            Ok(mut bindings) => {
+                for (player, defaults) in bindings.players.iter_mut().zip(Self::default().players) {
+                    player.analog.sanitize();
+                    player.unbind_reserved(&defaults);
+                }
+                bindings
+            }
+            Err(err) => {
+                warn!("Ignoring unreadable bindings file {}: {err}", path.display());
+                Self::default()
+            }
+        }
+    }
+
+    fn save(&self) {
+        let Some(path) = Self::config_path() else {
+            return;
+        };
+        save_ron(&path, "bindings", self);
+    }
+
+    /// Binds `input` to `target`, swapping with whatever already used it so no
+    /// input ever drives two actions. Keys are shared between both players but
+    /// pad buttons only clash within one player's own pad.
+    fn rebind(&mut self, target: RebindTarget, input: BoundInput) -> Option<(PlayerId, Action)> {
+        let previous = self.players[target.player.index()].get(target.action).input(target.device);
+
+        let mut conflict = None;
+        for player in [PlayerId::One, PlayerId::Two] {
+            if matches!(input, BoundInput::Button(_)) && player != target.player {
+                continue;
+            }
+            for action in Action::ALL {
+                if (player, action) == (target.player, target.action) {
+                    continue;
+                }
+                let binding = self.players[player.index()].get_mut(action);
+                if binding.input(target.device) == Some(input) {
+                    binding.set(target.device, previous);
+                    conflict = Some((player, action));
+                }
+            }
+        }
+
+        self.players[target.player.index()]
+            .get_mut(target.action)
+            .set(target.device, Some(input));
+        conflict
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Device {
+    Keyboard,
+    Gamepad,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum BoundInput {
+    Key(KeyCode),
+    Button(GamepadButton),
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+struct RebindTarget {
+    player: PlayerId,
+    action: Action,
+    device: Device,
+}
+
+#[derive(Component)]
+struct ResetBindingsButton;
+
+#[derive(Component)]
+struct SteeringModeButton(PlayerId);
+
+#[derive(Component)]
+struct RebindStatusText;
+
+/// The binding waiting for its next key or button press
+#[derive(Resource, Default)]
+struct Rebinding {
+    target: Option<RebindTarget>,
+    status: String,
+}
+
+fn input_label(input: Option<BoundInput>) -> String {
+    let Some(input) = input else {
+        return "-".to_string();
+    };
+    let name = match input {
+        BoundInput::Key(key) => format!("{key:?}"),
+        BoundInput::Button(button) => format!("{button:?}"),
+    };
+    name.trim_start_matches("Key").trim_start_matches("Digit").to_string()
+}
+
+fn spawn_controls_menu(mut commands: Commands) {
+    let columns = [
+        (PlayerId::One, Device::Keyboard, "Red Keys"),
+        (PlayerId::One, Device::Gamepad, "Red Pad"),
+        (PlayerId::Two, Device::Keyboard, "Blue Keys"),
+        (PlayerId::Two, Device::Gamepad, "Blue Pad"),
+    ];
+
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(8.0),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.05, 0.9)),
+            DespawnOnExit(GameState::Controls),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new("Controls"),
+                TextFont { font_size: 48.0, ..default() },
+                TextColor(Color::WHITE),
+            ));
+            parent
+                .spawn(Node {
+                    flex_direction: FlexDirection::Row,
+                    column_gap: Val::Px(8.0),
+                    ..default()
+                })
+                .with_children(|row| {
+                    spawn_cell_label(row, "");
+                    for (_, _, heading) in columns {
+                        spawn_cell_label(row, heading);
+                    }
+                });
+            for action in Action::ALL {
+                parent
+                    .spawn(Node {
+                        flex_direction: FlexDirection::Row,
+                        column_gap: Val::Px(8.0),
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        spawn_cell_label(row, action.label());
+                        for (player, device, _) in columns {
+                            row.spawn((
+                                Button,
+                                Node {
+                                    width: Val::Px(140.0),
+                                    height: Val::Px(36.0),
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                BackgroundColor(crate::NORMAL_BUTTON),
+                                RebindTarget { player, action, device },
+                                children![(
+                                    Text::default(),
+                                    TextFont { font_size: 18.0, ..default() },
+                                    TextColor(Color::WHITE),
+                                )],
+                            ));
+                        }
+                    });
+            }
+            parent
+                .spawn(Node {
+                    flex_direction: FlexDirection::Row,
+                    column_gap: Val::Px(8.0),
+                    ..default()
+                })
+                .with_children(|row| {
+                    spawn_cell_label(row, "Stick Steering");
+                    for player in [PlayerId::One, PlayerId::Two] {
+                        spawn_cell_label(row, "");
+                        row.spawn((
+                            Button,
+                            Node {
+                                width: Val::Px(140.0),
+                                height: Val::Px(36.0),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            BackgroundColor(crate::NORMAL_BUTTON),
+                            SteeringModeButton(player),
+                            children![(
+                                Text::default(),
+                                TextFont { font_size: 18.0, ..default() },
+                                TextColor(Color::WHITE),
+                            )],
+                        ));
+                    }
+                });
+            parent.spawn((
+                Text::new("Dead zones and response curves live in bindings.ron"),
+                TextFont { font_size: 16.0, ..default() },
+                TextColor(Color::srgb(0.5, 0.5, 0.6)),
+            ));
+            parent.spawn((
+                Text::new("Click a binding, then press the new key or button. Esc cancels or goes back."),
+                TextFont { font_size: 18.0, ..default() },
+                TextColor(Color::srgb(0.7, 0.7, 0.8)),
+                RebindStatusText,
+            ));
+            parent
+                .spawn((
+                    Button,
+                    Node {
+                        width: Val::Px(260.0),
+                        height: Val::Px(56.0),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    BackgroundColor(crate::NORMAL_BUTTON),
+                    ResetBindingsButton,
+                ))
+                .with_children(|button| {
+                    button.spawn((
+                        Text::new("Reset to Defaults"),
+                        TextFont { font_size: 28.0, ..default() },
+                        TextColor(Color::WHITE),
+                    ));
+                });
+            spawn_menu_button(parent, "Back", MenuAction::MainMenu);
+        });
+}
+
+fn spawn_cell_label(row: &mut ChildSpawnerCommands, label: &str) {
+    row.spawn((
+        Node {
+            width: Val::Px(140.0),
+            height: Val::Px(36.0),
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        children![(
+            Text::new(label),
+            TextFont { font_size: 18.0, ..default() },
+            TextColor(Color::srgb(0.7, 0.7, 0.8)),
+        )],
+    ));
+}
+
+fn controls_buttons(
+    rebind_buttons: Query<(&Interaction, &RebindTarget), Changed<Interaction>>,
+    reset_buttons: Query<&Interaction, (Changed<Interaction>, With<ResetBindingsButton>)>,
+    steering_buttons: Query<(&Interaction, &SteeringModeButton), Changed<Interaction>>,
+    mut bindings: ResMut<ControlBindings>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    for (interaction, SteeringModeButton(player)) in &steering_buttons {
+        if *interaction == Interaction::Pressed {
+            let analog = &mut bindings.players[player.index()].analog;
+            analog.steering = match analog.steering {
+                SteeringMode::Tank => SteeringMode::PointToFace,
+                SteeringMode::PointToFace => SteeringMode::Tank,
+            };
+            bindings.save();
+        }
+    }
+    for (interaction, target) in &rebind_buttons {
+        if *interaction == Interaction::Pressed {
+            rebinding.target = Some(*target);
+            rebinding.status = format!("Press a new {} for {}", match target.device {
+                Device::Keyboard => "key",
+                Device::Gamepad => "pad button",
+            }, target.action.label());
+        }
+    }
+    for interaction in &reset_buttons {
+        if *interaction == Interaction::Pressed {
+            *bindings = ControlBindings::default();
+            bindings.save();
+            rebinding.target = None;
+            rebinding.status = "Controls reset to defaults".to_string();
+        }
+    }
+}
+
+fn listen_for_binding(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut bindings: ResMut<ControlBindings>,
+    mut rebinding: ResMut<Rebinding>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(target) = rebinding.target else {
+        if keyboard.just_pressed(KeyCode::Escape) {
+            next_state.set(GameState::MainMenu);
+        }
+        return;
+    };
+    // Escape cancels here, and like the other menu keys can't be bound
+    if keyboard.just_pressed(KeyCode::Escape) {
+        rebinding.target = None;
+        rebinding.status = "Cancelled".to_string();
+        return;
+    }
+
+    let pressed = match target.device {
+        Device::Keyboard => keyboard.get_just_pressed().next().copied().map(BoundInput::Key),
+        Device::Gamepad => gamepads
+            .iter()
+            .find_map(|gp| gp.get_just_pressed().next().copied())
+            .map(BoundInput::Button),
+    };
+    let Some(input) = pressed else {
+        return;
+    };
+    let reserved = match input {
+        BoundInput::Key(key) => RESERVED_KEYS.contains(&key),
+        BoundInput::Button(button) => RESERVED_BUTTONS.contains(&button),
+    };
+    if reserved {
+        // Still listening, so the next press can pick something else
+        rebinding.status = format!("{} is already in use by the menus, pick another", input_label(Some(input)));
+        return;
+    }
+
+    let swapped = bindings.rebind(target, input);
+    bindings.save();
+    rebinding.target = None;
+    rebinding.status = match swapped {
+        Some((player, action)) => format!(
+            "{} was already used by {} {}, swapped",
+            input_label(Some(input)),
+            match player {
+                PlayerId::One => "Red",
+                PlayerId::Two => "Blue",
+            },
+            action.label(),
+        ),
+        None => format!("{} bound to {}", input_label(Some(input)), target.action.label()),
+    };
+}
+
+fn update_binding_labels(
+    bindings: Res<ControlBindings>,
+    rebinding: Res<Rebinding>,
+    buttons: Query<(&RebindTarget, &Children)>,
+    steering_buttons: Query<(&SteeringModeButton, &Children)>,
+    mut texts: Query<&mut Text, Without<RebindStatusText>>,
+    mut status: Query<&mut Text, With<RebindStatusText>>,
+) {
+    for (SteeringModeButton(player), children) in &steering_buttons {
+        let label = match bindings.player(*player).analog.steering {
+            SteeringMode::Tank => "Tank",
+            SteeringMode::PointToFace => "Point to Face",
+        };
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(child) {
+                text.0 = label.to_string();
+            }
+        }
+    }
+    for (target, children) in &buttons {
+        let label = if rebinding.target == Some(*target) {
+            "...".to_string()
+        } else {
+            input_label(bindings.player(target.player).get(target.action).input(target.device))
+        };
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(child) {
+                text.0 = label.clone();
+            }
+        }
+    }
+    if !rebinding.status.is_empty() {
+        for mut text in &mut status {
+            text.0 = rebinding.status.clone();
+        }
+    }
+}
+
+fn stop_listening(mut rebinding: ResMut<Rebinding>) {
+    *rebinding = Rebinding::default();
+}
@@ -0,0 +1,328 @@
+use bevy::{ecs::system::SystemParam, input::gamepad::*, prelude::*};
+
+use crate::{
+    bindings::{ActionBinding, ControlBindings, PlayerBindings},
+    tournament::Tournament,
+    GameState, MatchScore, PlayerId,
+};
+
+const PLAYERS: [PlayerId; 2] = [PlayerId::One, PlayerId::Two];
+
+pub struct ControllersPlugin;
+
+impl Plugin for ControllersPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PlayerControllers>()
+            .add_systems(
+                OnEnter(GameState::PlayerSelect),
+                (release_all_slots, spawn_player_select).chain(),
+            )
+            .add_systems(OnEnter(GameState::Paused), spawn_controller_status)
+            .add_systems(
+                Update,
+                (
+                    handle_connection,
+                    player_select_input.run_if(in_state(GameState::PlayerSelect)),
+                    claim_slots.run_if(in_state(GameState::PlayerSelect).or(in_state(GameState::Paused))),
+                    start_when_full.run_if(in_state(GameState::PlayerSelect)),
+                    pause_on_missing_controller.run_if(in_state(GameState::Playing)),
+                    update_slot_text.run_if(resource_changed::<PlayerControllers>),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// A device that can drive one ship
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InputSource {
+    /// One half of the keyboard, using the key bindings of the given player
+    Keyboard(PlayerId),
+    Gamepad(Entity),
+}
+
+/// Which device is driving each ship
+#[derive(Resource)]
+pub struct PlayerControllers {
+    slots: [Option<InputSource>; 2],
+    /// Pads that dropped out of a slot, so they get it back when they reconnect
+    dropped: [Option<Entity>; 2],
+}
+
+impl Default for PlayerControllers {
+    // Both keyboard halves until someone joins on the player select screen
+    fn default() -> Self {
+        Self {
+            slots: [
+                Some(InputSource::Keyboard(PlayerId::One)),
+                Some(InputSource::Keyboard(PlayerId::Two)),
+            ],
+            dropped: [None; 2],
+        }
+    }
+}
+
+impl PlayerControllers {
+    pub fn source(&self, id: PlayerId) -> Option<InputSource> {
+        self.slots[id.index()]
+    }
+
+    pub fn all_claimed(&self) -> bool {
+        self.slots.iter().all(Option::is_some)
+    }
+
+    /// Puts the device in the first open slot, unless it already holds one
+    fn claim(&mut self, source: InputSource) -> Option<PlayerId> {
+        if self.slots.contains(&Some(source)) {
+            return None;
+        }
+        let index = self.slots.iter().position(Option::is_none)?;
+        self.slots[index] = Some(source);
+        self.dropped[index] = None;
+        Some(PLAYERS[index])
+    }
+
+    fn release(&mut self, source: InputSource) -> Option<PlayerId> {
+        let index = self.slots.iter().position(|slot| *slot == Some(source))?;
+        self.slots[index] = None;
+        Some(PLAYERS[index])
+    }
+}
+
+/// Reads a player's bound actions from whichever device holds their slot
+#[derive(SystemParam)]
+pub struct PlayerActions<'w, 's> {
+    keyboard: Res<'w, ButtonInput<KeyCode>>,
+    bindings: Res<'w, ControlBindings>,
+    controllers: Res<'w, PlayerControllers>,
+    gamepads: Query<'w, 's, &'static Gamepad>,
+}
+
+impl PlayerActions<'_, '_> {
+    pub fn bindings(&self, id: PlayerId) -> &PlayerBindings {
+        self.bindings.player(id)
+    }
+
+    pub fn gamepad(&self, id: PlayerId) -> Option<&Gamepad> {
+        match self.controllers.source(id)? {
+            InputSource::Gamepad(entity) => self.gamepads.get(entity).ok(),
+            InputSource::Keyboard(_) => None,
+        }
+    }
+
+    /// A keyboard half keeps its own keys whichever ship it ends up flying,
+    /// while pad buttons follow the ship
+    fn keys(&self, id: PlayerId) -> Option<&PlayerBindings> {
+        match self.controllers.source(id)? {
+            InputSource::Keyboard(half) => Some(self.bindings.player(half)),
+            InputSource::Gamepad(_) => None,
+        }
+    }
+
+    pub fn pressed(&self, id: PlayerId, action: fn(&PlayerBindings) -> &ActionBinding) -> bool {
+        self.keys(id).and_then(|k| action(k).key).is_some_and(|key| self.keyboard.pressed(key))
+            || action(self.bindings(id))
+                .button
+                .zip(self.gamepad(id))
+                .is_some_and(|(button, gp)| gp.pressed(button))
+    }
+
+    pub fn just_pressed(&self, id: PlayerId, action: fn(&PlayerBindings) -> &ActionBinding) -> bool {
+        self.keys(id).and_then(|k| action(k).key).is_some_and(|key| self.keyboard.just_pressed(key))
+            || action(self.bindings(id))
+                .button
+                .zip(self.gamepad(id))
+                .is_some_and(|(button, gp)| gp.just_pressed(button))
+    }
+}
+
+#[derive(Component)]
+struct SlotText;
+
+fn handle_connection(
+    mut events: MessageReader<GamepadConnectionEvent>,
+    mut controllers: ResMut<PlayerControllers>,
+) {
+    for event in events.read() {
+        let pad = event.gamepad;
+        match &event.connection {
+            GamepadConnection::Connected { name, .. } => {
+                let Some(index) = controllers.dropped.iter().position(|e| *e == Some(pad)) else {
+                    continue;
+                };
+                if controllers.slots[index].is_none() {
+                    controllers.slots[index] = Some(InputSource::Gamepad(pad));
+                    info!("{name} reclaimed player {} slot", index + 1);
+                }
+                controllers.dropped[index] = None;
+            }
+            GamepadConnection::Disconnected => {
+                if let Some(id) = controllers.release(InputSource::Gamepad(pad)) {
+                    controllers.dropped[id.index()] = Some(pad);
+                    info!("Player {} lost their gamepad", id.index() + 1);
+                }
+            }
+        }
+    }
+}
+
+fn release_all_slots(mut controllers: ResMut<PlayerControllers>) {
+    controllers.slots = [None; 2];
+    controllers.dropped = [None; 2];
+}
+
+fn player_select_input(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<(Entity, &Gamepad)>,
+    tournament: Option<Res<Tournament>>,
+    mut controllers: ResMut<PlayerControllers>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard.just_pressed(KeyCode::Escape) {
+        // Back to the bracket when a tournament match is waiting on players
+        next_state.set(if tournament.is_some() { GameState::Bracket } else { GameState::MainMenu });
+    }
+    if keyboard.just_pressed(KeyCode::Backspace) {
+        controllers.slots = [None; 2];
+    }
+    for (entity, gamepad) in &gamepads {
+        if gamepad.just_pressed(GamepadButton::East) {
+            controllers.release(InputSource::Gamepad(entity));
+        }
+    }
+}
+
+/// Start on a pad or Fire on a keyboard half takes the next open slot
+pub fn claim_slots(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    bindings: Res<ControlBindings>,
+    gamepads: Query<(Entity, &Gamepad)>,
+    mut controllers: ResMut<PlayerControllers>,
+) {
+    if controllers.all_claimed() {
+        return;
+    }
+    for (entity, gamepad) in &gamepads {
+        if gamepad.just_pressed(GamepadButton::Start) {
+            controllers.claim(InputSource::Gamepad(entity));
+        }
+    }
+    for half in PLAYERS {
+        let fire = bindings.player(half).fire.key;
+        if fire.is_some_and(|key| keyboard.just_pressed(key)) {
+            controllers.claim(InputSource::Keyboard(half));
+        }
+    }
+}
+
+fn start_when_full(controllers: Res<PlayerControllers>, mut next_state: ResMut<NextState<GameState>>) {
+    if controllers.all_claimed() {
+        next_state.set(GameState::ShipSelect);
+    }
+}
+
+fn pause_on_missing_controller(
+    controllers: Res<PlayerControllers>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !controllers.all_claimed() {
+        next_state.set(GameState::Paused);
+    }
+}
+
+fn source_label(source: InputSource, names: &Query<&Name>) -> String {
+    match source {
+        InputSource::Keyboard(PlayerId::One) => "Keyboard (left)".to_string(),
+        InputSource::Keyboard(PlayerId::Two) => "Keyboard (right)".to_string(),
+        InputSource::Gamepad(entity) => names
+            .get(entity)
+            .map(|name| name.to_string())
+            .unwrap_or_else(|_| "Gamepad".to_string()),
+    }
+}
+
+fn slot_lines(controllers: &PlayerControllers, score: &MatchScore, names: &Query<&Name>) -> Vec<String> {
+    PLAYERS
+        .iter()
+        .map(|id| {
+            let device = match controllers.source(*id) {
+                Some(source) => source_label(source, names),
+                None if controllers.dropped[id.index()].is_some() => "Reconnect controller...".to_string(),
+                None => "Press Start to join".to_string(),
+            };
+            format!("{}: {device}", score.name(*id))
+        })
+        .collect()
+}
+
+fn spawn_player_select(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(16.0),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.05, 0.85)),
+        DespawnOnExit(GameState::PlayerSelect),
+        children![
+            (
+                Text::new("Join"),
+                TextFont { font_size: 64.0, ..default() },
+                TextColor(Color::WHITE),
+            ),
+            (
+                Text::default(),
+                TextFont { font_size: 32.0, ..default() },
+                TextColor(Color::WHITE),
+                SlotText,
+            ),
+            (
+                Text::new(
+                    "Start on a gamepad or Fire on a keyboard half to join\n\
+                     B to leave, Backspace to clear, Esc to go back",
+                ),
+                TextFont { font_size: 20.0, ..default() },
+                TextColor(Color::srgb(0.7, 0.7, 0.8)),
+            ),
+        ],
+    ));
+}
+
+fn spawn_controller_status(
+    mut commands: Commands,
+    controllers: Res<PlayerControllers>,
+    score: Res<MatchScore>,
+    names: Query<&Name>,
+) {
+    if controllers.all_claimed() {
+        return;
+    }
+    commands.spawn((
+        Text::new(slot_lines(&controllers, &score, &names).join("\n")),
+        TextFont { font_size: 24.0, ..default() },
+        TextColor(Color::srgb(1.0, 0.8, 0.3)),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(24.0),
+            left: Val::Px(24.0),
+            ..default()
+        },
+        SlotText,
+        DespawnOnExit(GameState::Paused),
+    ));
+}
+
+fn update_slot_text(
+    controllers: Res<PlayerControllers>,
+    score: Res<MatchScore>,
+    names: Query<&Name>,
+    mut texts: Query<&mut Text, With<SlotText>>,
+) {
+    for mut text in &mut texts {
+        text.0 = slot_lines(&controllers, &score, &names).join("\n");
+    }
+}
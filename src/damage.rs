@@ -0,0 +1,178 @@
+use bevy::prelude::*;
+use bevy_kira_audio::{Audio, AudioControl, AudioSource};
+
+use crate::{
+    physics::PhysicsSet, tuning::GameTuning, Health, MatchStats, Player, PlayerId, Shield,
+    ShieldFlicker, ShieldHealth,
+};
+
+pub struct DamagePlugin;
+
+impl Plugin for DamagePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<DamageEvent>()
+            .add_message::<ShipDestroyed>()
+            .add_systems(Startup, load_damage_sounds)
+            .add_systems(
+                FixedUpdate,
+                (apply_damage, tick_invulnerability).chain().in_set(PhysicsSet::Damage),
+            )
+            .add_systems(Update, play_destroyed_sound.run_if(on_message::<ShipDestroyed>));
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DamageKind {
+    Projectile,
+    /// Ramming another ship or an asteroid
+    Impact,
+    /// Mines, which go off under the hull
+    Explosion,
+}
+
+impl DamageKind {
+    /// Whether a raised shield can get in the way. It stops shots; rams and
+    /// mines land on the hull.
+    fn shieldable(self) -> bool {
+        self == DamageKind::Projectile
+    }
+}
+
+/// A hit on a ship, sent by whatever caused it and settled in `apply_damage`
+#[derive(Message)]
+pub struct DamageEvent {
+    pub target: Entity,
+    /// Player credited with the damage, if anyone
+    pub source: Option<PlayerId>,
+    pub amount: i32,
+    pub kind: DamageKind,
+}
+
+/// A ship's hull ran out. Sent once, as the ship is despawned.
+#[derive(Message)]
+pub struct ShipDestroyed {
+    pub ship: PlayerId,
+    pub killer: Option<PlayerId>,
+    pub position: Vec3,
+    pub radius: f32,
+}
+
+/// Recently knocked about, so further impacts do no damage until the timer runs out
+#[derive(Component)]
+pub struct Invulnerable {
+    timer: Timer,
+}
+
+/// Plating that takes a flat amount off every hit that gets past the shield
+#[derive(Component, Clone, Copy, Default)]
+pub struct Armor(pub i32);
+
+#[derive(Resource)]
+struct DamageSounds {
+    destroyed: Handle<AudioSource>,
+}
+
+fn load_damage_sounds(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(DamageSounds {
+        destroyed: asset_server.load("sounds/explosion.wav"),
+    });
+}
+
+/// The one place hull is lost: applies invulnerability, then a raised shield,
+/// then armor, then health, and destroys ships that run out
+fn apply_damage(
+    mut commands: Commands,
+    mut hits: MessageReader<DamageEvent>,
+    mut destroyed: MessageWriter<ShipDestroyed>,
+    mut ships: Query<(
+        &PlayerId,
+        &Transform,
+        &Player,
+        &mut Health,
+        &mut ShieldHealth,
+        Option<&Children>,
+        Option<&Armor>,
+        Has<Invulnerable>,
+    )>,
+    shields: Query<(), With<Shield>>,
+    mut stats: ResMut<MatchStats>,
+    tuning: Res<GameTuning>,
+) {
+    // Invulnerability handed out this tick, which the query won't see until next tick
+    let mut reeling = Vec::new();
+
+    for hit in hits.read() {
+        let Ok((id, transform, ship, mut health, mut shield, children, armor, invulnerable)) =
+            ships.get_mut(hit.target)
+        else {
+            continue;
+        };
+        // Already destroyed earlier this tick
+        if health.hp <= 0 || hit.amount <= 0 {
+            continue;
+        }
+        if hit.kind == DamageKind::Impact {
+            if invulnerable || reeling.contains(&hit.target) {
+                continue;
+            }
+            reeling.push(hit.target);
+            commands.entity(hit.target).insert(Invulnerable {
+                timer: Timer::from_seconds(tuning.impact_invulnerability, TimerMode::Once),
+            });
+        }
+
+        // A raised shield soaks up as much as its points pay for, the rest gets through
+        let mut amount = hit.amount;
+        let raised =
+            children.and_then(|children| children.iter().find(|&child| shields.contains(child)));
+        if let Some(bubble) = raised.filter(|_| hit.kind.shieldable() && shield.shp > 0.0) {
+            let absorbed = (amount as f32).min(shield.shp / tuning.shield_cost.max(f32::EPSILON));
+            let spent = (absorbed * tuning.shield_cost).min(shield.shp);
+            shield.shp -= spent;
+            amount = (amount as f32 - absorbed).round() as i32;
+            stats.players[id.index()].damage_absorbed += absorbed;
+            commands.entity(bubble).insert(ShieldFlicker::new());
+        }
+        amount -= armor.map_or(0, |armor| armor.0.max(0));
+        if amount <= 0 {
+            continue;
+        }
+
+        health.hp -= amount;
+        if let Some(source) = hit.source.filter(|source| source != id) {
+            stats.players[source.index()].damage_dealt += amount;
+        }
+
+        if health.hp <= 0 {
+            destroyed.write(ShipDestroyed {
+                ship: *id,
+                killer: hit.source.filter(|source| source != id),
+                position: transform.translation,
+                radius: ship.radius,
+            });
+            commands.entity(hit.target).despawn();
+        }
+    }
+}
+
+/// Counts down a ship's grace period after an impact, blinking it meanwhile
+fn tick_invulnerability(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Invulnerable, &mut Sprite)>,
+) {
+    for (entity, mut invulnerable, mut sprite) in &mut query {
+        invulnerable.timer.tick(time.delta());
+        if invulnerable.timer.is_finished() {
+            sprite.color.set_alpha(1.0);
+            commands.entity(entity).remove::<Invulnerable>();
+        } else {
+            let blink = ((invulnerable.timer.elapsed_secs() * 12.0) as u32).is_multiple_of(2);
+            sprite.color.set_alpha(if blink { 0.35 } else { 1.0 });
+        }
+    }
+}
+
+fn play_destroyed_sound(audio: Res<Audio>, sounds: Res<DamageSounds>) {
+    audio.play(sounds.destroyed.clone());
+}
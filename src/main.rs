@@ -1,3 +1,6 @@
+// Systems take their queries and resources as arguments, so these grow with the game
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
 use bevy::{
     prelude::*,
     camera::visibility::RenderLayers,
@@ -7,10 +10,37 @@ use bevy::{
     },
     window::WindowResized,
 };
+use std::{fs, path::Path, time::Duration};
+
 use rand::Rng;
 use rand::rng;
-use bevy::input::gamepad::*;
+use bevy::input::InputSystems;
 use bevy_kira_audio::{Audio, AudioControl, AudioPlugin, AudioSource};
+
+mod arena;
+mod asteroids;
+mod benchmark;
+mod bindings;
+mod controllers;
+mod damage;
+mod physics;
+mod ships;
+mod tournament;
+mod tuning;
+mod weapons;
+
+use arena::{Arena, ArenaPlugin, Wraps};
+use asteroids::{AsteroidDestroyed, AsteroidFactory, AsteroidShape, AsteroidSize, AsteroidsPlugin};
+use benchmark::BenchmarkPlugin;
+use bindings::{AnalogSettings, BindingsPlugin, SteeringMode};
+use controllers::{ControllersPlugin, PlayerActions, PlayerControllers};
+use damage::{Armor, DamageEvent, DamageKind, DamagePlugin, ShipDestroyed};
+use physics::{sweep_entry, BroadPhase, Interpolated, PhysicsPlugin, PhysicsSet};
+use ships::{Loadout, ShieldStats, ShipChoices, ShipDef, ShipRoster, ShipsPlugin};
+use tournament::{Tournament, TournamentPlugin};
+use tuning::{GameTuning, TuningPlugin};
+use weapons::{EquippedWeapon, WeaponsPlugin};
+
 const RES_WIDTH: u32 = 1200;
 const RES_HEIGHT: u32 = 640;
 const HIGH_RES_LAYERS: RenderLayers = RenderLayers::layer(1);
@@ -20,52 +50,97 @@ fn main() {
     App::new()
         .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
         .add_plugins(AudioPlugin)
+        .add_plugins((ArenaPlugin, AsteroidsPlugin, BenchmarkPlugin, BindingsPlugin, ControllersPlugin, DamagePlugin, PhysicsPlugin, ShipsPlugin, TournamentPlugin, TuningPlugin, WeaponsPlugin))
         .insert_resource(Time::<Fixed>::from_hz(60.0))
+        .init_state::<GameState>()
         .init_resource::<AsteroidSpawner>()
-        .init_resource::<PlayerControllers>()
-        .add_event::<ResetGameEvent>()
+        .init_resource::<MatchScore>()
+        .init_resource::<MatchStats>()
+        .add_message::<ResetGameEvent>()
         .add_systems(
             Startup, 
             (
-                setup_camera, setup, load_audio_assets, setup_asteroid_spawning,
+                setup_camera, load_audio_assets, setup_asteroid_spawning,
             ).chain()
         )
+        .add_systems(
+            OnEnter(GameState::MainMenu),
+            (despawn_game_entities, reset_player_names, spawn_main_menu).chain(),
+        )
+        .add_systems(OnExit(GameState::MainMenu), reset_match_score)
+        .add_systems(OnExit(GameState::MatchOver), reset_match_score)
+        .add_systems(
+            OnEnter(GameState::Playing),
+            (reset_game_system, setup).chain().run_if(starting_round),
+        )
+        .add_systems(OnEnter(GameState::Paused), (spawn_pause_menu, pause_audio))
+        .add_systems(OnExit(GameState::Paused), resume_audio)
+        .add_systems(OnEnter(GameState::RoundOver), spawn_round_over_banner)
+        .add_systems(OnEnter(GameState::MatchOver), spawn_match_over_screen)
+        .add_systems(
+            PreUpdate,
+            collect_ship_input
+                .after(InputSystems)
+                .run_if(in_state(GameState::Playing)),
+        )
         .add_systems(
             FixedUpdate,
             (
-                rotation,
-                thrust,
-                fire_laser,
-                warp_drive,
-                shield_system,
-                //shield_system_controller,
+                (
+                    rotation,
+                    thrust,
+                    (reload_weapon, fire_laser).chain(),
+                    warp_drive,
+                    shield_system,
+                    spawn_asteroid,
+                ).in_set(PhysicsSet::Input),
+                (
+                    move_player,
+                    projectile_movement,
+                    move_asteroids,
+                ).in_set(PhysicsSet::Integrate),
+                (
+                    player_player_collision,
+                    asteroid_asteroid_collision,
+                    ship_asteroid_collision,
+                    ship_nickel_collision,
+                    projectile_asteroid_collision,
+                    // A shot the shield stops never reaches the hull
+                    (projectile_shield_collision, projectile_player_collision).chain(),
+                ).in_set(PhysicsSet::Collide),
             )
         )
         .add_systems(
             Update, 
             ( 
                 fit_canvas,
-                handle_connection,
-                thrust_controller,
-                rotation_controller,
-                fire_laser_controller,
-                warp_drive_controller,
-                move_player, 
-                projectile_movement, 
-                projectile_player_collision,
-                player_player_collision,
                 update_health_ui,
                 update_shield_ui,
-                move_asteroids,            
-                projectile_shield_collision,
-                spawn_asteroid,
-                projectile_asteroid_collision,
-                ship_asteroid_collision,
-                reset_key_system,
-                reset_game_system,
-                ship_nickel_collision,
+                flicker_shields,
+                fade_parry_flashes,
+                update_ammo_ui,
+                button_color_system,
+                menu_button_system,
+                main_menu_input.run_if(in_state(GameState::MainMenu)),
+                update_settings_labels.run_if(resource_changed::<MatchScore>.or(resource_changed::<Arena>)),
+                pause_input
+                    .after(controllers::claim_slots)
+                    .run_if(in_state(GameState::Playing).or(in_state(GameState::Paused))),
+                round_over_countdown.run_if(in_state(GameState::RoundOver)),
+                match_over_input.run_if(in_state(GameState::MatchOver)),
             )
         )
+        .add_systems(
+            Update, 
+            ( 
+                reset_key_system,
+                restart_round,
+                // Once a frame, however many ticks ran, so a round is only scored once
+                check_round_end.run_if(on_message::<ShipDestroyed>),
+                clear_destroyed_health_ui,
+                record_kills,
+            ).run_if(in_state(GameState::Playing))
+        )
         .run();
 }
 
@@ -115,7 +190,7 @@ fn setup_camera(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
             InGameCamera,
             ));
     commands.spawn((Sprite::from_image(image_handle), Canvas, HIGH_RES_LAYERS));
-    commands.spawn((Camera2d, Msaa::Off, OuterCamera, HIGH_RES_LAYERS));
+    commands.spawn((Camera2d, Msaa::Off, OuterCamera, HIGH_RES_LAYERS, IsDefaultUiCamera));
 }
 
 fn fit_canvas(
@@ -132,30 +207,589 @@ fn fit_canvas(
     }
 }
 
+#[derive(States, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+enum GameState {
+    #[default]
+    MainMenu,
+    Playing,
+    Paused,
+    RoundOver,
+    MatchOver,
+    TournamentLobby,
+    Bracket,
+    Controls,
+    PlayerSelect,
+    ShipSelect,
+}
+
 #[derive(Message)]
 struct ResetGameEvent;
 
+/// Round wins for the current best-of-N match
+#[derive(Resource)]
+struct MatchScore {
+    names: [String; 2],
+    best_of: u32,
+    round: u32,
+    wins: [u32; 2],
+    round_winner: Option<PlayerId>,
+    match_winner: Option<PlayerId>,
+}
+
+impl Default for MatchScore {
+    fn default() -> Self {
+        Self {
+            names: ["Red".to_string(), "Blue".to_string()],
+            best_of: 3,
+            round: 1,
+            wins: [0, 0],
+            round_winner: None,
+            match_winner: None,
+        }
+    }
+}
+
+impl MatchScore {
+    fn wins_needed(&self) -> u32 {
+        self.best_of / 2 + 1
+    }
+
+    fn name(&self, id: PlayerId) -> &str {
+        &self.names[id.index()]
+    }
+
+    fn wins(&self, id: PlayerId) -> u32 {
+        self.wins[id.index()]
+    }
+
+    // 3 -> 5 -> 7 -> 3
+    fn cycle_best_of(&mut self) {
+        self.best_of = match self.best_of {
+            3 => 5,
+            5 => 7,
+            _ => 3,
+        };
+    }
+
+    fn reset(&mut self) {
+        *self = Self {
+            names: std::mem::take(&mut self.names),
+            best_of: self.best_of,
+            ..default()
+        };
+    }
+}
+
+#[derive(Default)]
+struct PlayerStats {
+    shots_fired: u32,
+    shots_hit: u32,
+    damage_dealt: i32,
+    damage_absorbed: f32,
+    shots_parried: u32,
+    asteroids_destroyed: u32,
+    nickels_collected: u32,
+    ships_destroyed: u32,
+}
+
+impl PlayerStats {
+    fn accuracy(&self) -> f32 {
+        if self.shots_fired == 0 {
+            return 0.0;
+        }
+        (self.shots_hit as f32 / self.shots_fired as f32).min(1.0)
+    }
+}
+
+/// Running totals for the results screen, cleared when a new match starts
+#[derive(Resource, Default)]
+struct MatchStats {
+    players: [PlayerStats; 2],
+}
+
+#[derive(Resource)]
+struct RoundOverTimer(Timer);
+
+#[derive(Component, Clone, Copy)]
+enum MenuAction {
+    Play,
+    CycleBestOf,
+    ToggleWrap,
+    Resume,
+    Rematch,
+    Tournament,
+    ResumeTournament,
+    ContinueTournament,
+    Controls,
+    MainMenu,
+    Quit,
+}
+
+const NORMAL_BUTTON: Color = Color::srgb(0.15, 0.15, 0.2);
+const HOVERED_BUTTON: Color = Color::srgb(0.25, 0.25, 0.35);
+const PRESSED_BUTTON: Color = Color::srgb(0.35, 0.55, 0.35);
+
+// Coming back from the pause menu should not restart the round
+fn starting_round(mut transitions: MessageReader<StateTransitionEvent<GameState>>) -> bool {
+    transitions
+        .read()
+        .last()
+        .is_none_or(|transition| transition.exited != Some(GameState::Paused))
+}
+
 fn reset_game_system(
     mut commands: Commands,
-    mut reset_reader: EventReader<ResetGameEvent>,
     game_entities: Query<Entity, With<GameEntity>>,
-    asset_server: Res<AssetServer>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    query: Query<(&PlayerId, &Health)>
+    mut spawner: ResMut<AsteroidSpawner>,
+    mut score: ResMut<MatchScore>,
+) {
+    // Despawn everything that belongs to the game, setup runs right after
+    for entity in &game_entities {
+        commands.entity(entity).despawn();
+    }
+    spawner.timer.reset();
+    score.round_winner = None;
+}
+
+fn despawn_game_entities(
+    mut commands: Commands,
+    game_entities: Query<Entity, With<GameEntity>>,
+) {
+    for entity in &game_entities {
+        commands.entity(entity).despawn();
+    }
+}
+
+fn spawn_menu(
+    commands: &mut Commands,
+    state: GameState,
+    title: &str,
+    subtitle: &str,
+    buttons: &[(&str, MenuAction)],
+) {
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(16.0),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.05, 0.7)),
+            DespawnOnExit(state),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(title),
+                TextFont { font_size: 64.0, ..default() },
+                TextColor(Color::WHITE),
+            ));
+            parent.spawn((
+                Text::new(subtitle),
+                TextFont { font_size: 24.0, ..default() },
+                TextColor(Color::srgb(0.7, 0.7, 0.8)),
+            ));
+            for (label, action) in buttons {
+                spawn_menu_button(parent, label, *action);
+            }
+        });
+}
+
+fn spawn_menu_button(parent: &mut ChildSpawnerCommands, label: &str, action: MenuAction) {
+    parent
+        .spawn((
+            Button,
+            Node {
+                width: Val::Px(260.0),
+                height: Val::Px(56.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(NORMAL_BUTTON),
+            action,
+        ))
+        .with_children(|button| {
+            button.spawn((
+                Text::new(label),
+                TextFont { font_size: 28.0, ..default() },
+                TextColor(Color::WHITE),
+            ));
+        });
+}
+
+fn best_of_label(score: &MatchScore) -> String {
+    format!("Best of {}", score.best_of)
+}
+
+fn spawn_main_menu(mut commands: Commands, score: Res<MatchScore>, arena: Res<Arena>) {
+    let best_of = best_of_label(&score);
+    let mut buttons = vec![
+        ("Play", MenuAction::Play),
+        (best_of.as_str(), MenuAction::CycleBestOf),
+        (arena.label(), MenuAction::ToggleWrap),
+        ("Tournament", MenuAction::Tournament),
+        ("Controls", MenuAction::Controls),
+    ];
+    if Tournament::has_save() {
+        buttons.push(("Resume Tournament", MenuAction::ResumeTournament));
+    }
+    buttons.push(("Quit", MenuAction::Quit));
+
+    spawn_menu(
+        &mut commands,
+        GameState::MainMenu,
+        "Kuiper Belt",
+        "Press Enter or Start to play, B or Select to change match length",
+        &buttons,
+    );
+}
+
+fn reset_player_names(mut score: ResMut<MatchScore>) {
+    score.names = MatchScore::default().names;
+}
+
+fn spawn_pause_menu(mut commands: Commands, controllers: Res<PlayerControllers>) {
+    let subtitle = if controllers.all_claimed() {
+        "Press Esc or Start to resume"
+    } else {
+        "Controller disconnected, reconnect it or press Start on another pad"
+    };
+    spawn_menu(
+        &mut commands,
+        GameState::Paused,
+        "Paused",
+        subtitle,
+        &[("Resume", MenuAction::Resume), ("Main Menu", MenuAction::MainMenu)],
+    );
+}
+
+fn score_line(score: &MatchScore) -> String {
+    format!(
+        "{} {} - {} {}",
+        score.name(PlayerId::One),
+        score.wins(PlayerId::One),
+        score.wins(PlayerId::Two),
+        score.name(PlayerId::Two),
+    )
+}
+
+fn spawn_round_over_banner(mut commands: Commands, score: Res<MatchScore>, tuning: Res<GameTuning>) {
+    commands.insert_resource(RoundOverTimer(Timer::from_seconds(tuning.round_over_delay, TimerMode::Once)));
+    let title = match score.round_winner {
+        Some(id) => format!("{} takes the round", score.name(id)),
+        None => "No winner this round".to_string(),
+    };
+    spawn_menu(&mut commands, GameState::RoundOver, &title, &score_line(&score), &[]);
+}
+
+fn spawn_stat_row(parent: &mut ChildSpawnerCommands, label: &str, red: String, blue: String, color: Color) {
+    parent
+        .spawn(Node {
+            flex_direction: FlexDirection::Row,
+            ..default()
+        })
+        .with_children(|row| {
+            for (text, width) in [(label.to_string(), 260.0), (red, 140.0), (blue, 140.0)] {
+                row.spawn((
+                    Node {
+                        width: Val::Px(width),
+                        ..default()
+                    },
+                    children![(
+                        Text::new(text),
+                        TextFont { font_size: 22.0, ..default() },
+                        TextColor(color),
+                    )],
+                ));
+            }
+        });
+}
+
+fn spawn_match_over_screen(
+    mut commands: Commands,
+    score: Res<MatchScore>,
+    stats: Res<MatchStats>,
+    tournament: Option<Res<Tournament>>,
+) {
+    let title = match score.match_winner {
+        Some(id) => format!("{} Wins!", score.name(id)),
+        None => "Draw!".to_string(),
+    };
+    let red = &stats.players[PlayerId::One.index()];
+    let blue = &stats.players[PlayerId::Two.index()];
+    let rows = [
+        ("Rounds won", score.wins(PlayerId::One).to_string(), score.wins(PlayerId::Two).to_string()),
+        ("Shots fired", red.shots_fired.to_string(), blue.shots_fired.to_string()),
+        ("Accuracy", format!("{:.0}%", red.accuracy() * 100.0), format!("{:.0}%", blue.accuracy() * 100.0)),
+        ("Damage dealt", red.damage_dealt.to_string(), blue.damage_dealt.to_string()),
+        ("Ships destroyed", red.ships_destroyed.to_string(), blue.ships_destroyed.to_string()),
+        ("Damage absorbed", format!("{:.0}", red.damage_absorbed), format!("{:.0}", blue.damage_absorbed)),
+        ("Shots parried", red.shots_parried.to_string(), blue.shots_parried.to_string()),
+        ("Asteroids destroyed", red.asteroids_destroyed.to_string(), blue.asteroids_destroyed.to_string()),
+        ("Nickels collected", red.nickels_collected.to_string(), blue.nickels_collected.to_string()),
+    ];
+
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(12.0),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.05, 0.85)),
+            DespawnOnExit(GameState::MatchOver),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(title),
+                TextFont { font_size: 64.0, ..default() },
+                TextColor(Color::WHITE),
+            ));
+            parent
+                .spawn(Node {
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(6.0),
+                    margin: UiRect::vertical(Val::Px(12.0)),
+                    ..default()
+                })
+                .with_children(|table| {
+                    spawn_stat_row(
+                        table,
+                        "",
+                        score.name(PlayerId::One).to_string(),
+                        score.name(PlayerId::Two).to_string(),
+                        Color::srgb(0.7, 0.7, 0.8),
+                    );
+                    for (label, red, blue) in rows {
+                        spawn_stat_row(table, label, red, blue, Color::WHITE);
+                    }
+                });
+            if tournament.is_some() {
+                spawn_menu_button(parent, "Continue", MenuAction::ContinueTournament);
+            } else {
+                spawn_menu_button(parent, "Rematch", MenuAction::Rematch);
+            }
+            spawn_menu_button(parent, "Main Menu", MenuAction::MainMenu);
+        });
+}
+
+fn pause_audio(audio: Res<Audio>) {
+    audio.pause();
+}
+
+fn resume_audio(audio: Res<Audio>) {
+    audio.resume();
+}
+
+fn button_color_system(
+    mut buttons: Query<(&Interaction, &mut BackgroundColor), (Changed<Interaction>, With<Button>)>,
+) {
+    for (interaction, mut color) in &mut buttons {
+        *color = match interaction {
+            Interaction::Pressed => PRESSED_BUTTON.into(),
+            Interaction::Hovered => HOVERED_BUTTON.into(),
+            Interaction::None => NORMAL_BUTTON.into(),
+        };
+    }
+}
+
+fn menu_button_system(
+    mut commands: Commands,
+    buttons: Query<(&Interaction, &MenuAction), (Changed<Interaction>, With<Button>)>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut score: ResMut<MatchScore>,
+    controllers: Res<PlayerControllers>,
+    mut arena: ResMut<Arena>,
+    mut exit: MessageWriter<AppExit>,
+) {
+    for (interaction, action) in &buttons {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match action {
+            MenuAction::Play => next_state.set(GameState::PlayerSelect),
+            MenuAction::Resume => {
+                if controllers.all_claimed() {
+                    next_state.set(GameState::Playing);
+                }
+            }
+            MenuAction::Rematch => next_state.set(GameState::ShipSelect),
+            MenuAction::CycleBestOf => score.cycle_best_of(),
+            MenuAction::ToggleWrap => arena.wrap = !arena.wrap,
+            MenuAction::Tournament => next_state.set(GameState::TournamentLobby),
+            MenuAction::ResumeTournament => {
+                if let Some(tournament) = Tournament::load() {
+                    commands.insert_resource(tournament);
+                    next_state.set(GameState::Bracket);
+                }
+            }
+            MenuAction::ContinueTournament => next_state.set(GameState::Bracket),
+            MenuAction::Controls => next_state.set(GameState::Controls),
+            MenuAction::MainMenu => next_state.set(GameState::MainMenu),
+            MenuAction::Quit => {
+                exit.write(AppExit::Success);
+            }
+        }
+    }
+}
+
+fn any_gamepad_just_pressed(gamepads: &Query<&Gamepad>, button: GamepadButton) -> bool {
+    gamepads.iter().any(|gp| gp.just_pressed(button))
+}
+
+/// Writes `value` to `path` as RON, making its directory first. Failing only
+/// logs, as losing a save shouldn't stop the game.
+fn save_ron(path: &Path, what: &str, value: &impl serde::Serialize) {
+    let result = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+        .map_err(|err| err.to_string())
+        .and_then(|contents| {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+            }
+            fs::write(path, contents).map_err(|err| err.to_string())
+        });
+    if let Err(err) = result {
+        warn!("Could not save {what} to {}: {err}", path.display());
+    }
+}
+
+fn main_menu_input(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut score: ResMut<MatchScore>,
+) {
+    if keyboard.just_pressed(KeyCode::Enter) || any_gamepad_just_pressed(&gamepads, GamepadButton::Start) {
+        next_state.set(GameState::PlayerSelect);
+    }
+    if keyboard.just_pressed(KeyCode::KeyB) || any_gamepad_just_pressed(&gamepads, GamepadButton::Select) {
+        score.cycle_best_of();
+    }
+}
+
+fn update_settings_labels(
+    score: Res<MatchScore>,
+    arena: Res<Arena>,
+    buttons: Query<(&MenuAction, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    for (action, children) in &buttons {
+        let label = match action {
+            MenuAction::CycleBestOf => best_of_label(&score),
+            MenuAction::ToggleWrap => arena.label().to_string(),
+            _ => continue,
+        };
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(child) {
+                text.0 = label.clone();
+            }
+        }
+    }
+}
+
+fn reset_match_score(mut score: ResMut<MatchScore>, mut stats: ResMut<MatchStats>) {
+    score.reset();
+    *stats = MatchStats::default();
+}
+
+fn pause_input(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    controllers: Res<PlayerControllers>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !keyboard.just_pressed(KeyCode::Escape) && !any_gamepad_just_pressed(&gamepads, GamepadButton::Start) {
+        return;
+    }
+    match state.get() {
+        GameState::Playing => next_state.set(GameState::Paused),
+        // Hold the match until every ship has someone on the sticks again
+        GameState::Paused if controllers.all_claimed() => next_state.set(GameState::Playing),
+        _ => {}
+    }
+}
+
+fn round_over_countdown(
+    time: Res<Time>,
+    mut timer: ResMut<RoundOverTimer>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    timer.0.tick(time.delta());
+    if timer.0.is_finished() {
+        next_state.set(GameState::Playing);
+    }
+}
+
+fn match_over_input(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    tournament: Option<Res<Tournament>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard.just_pressed(KeyCode::Enter) || any_gamepad_just_pressed(&gamepads, GamepadButton::Start) {
+        if tournament.is_some() {
+            next_state.set(GameState::Bracket);
+        } else {
+            next_state.set(GameState::ShipSelect);
+        }
+    } else if keyboard.just_pressed(KeyCode::Escape) || any_gamepad_just_pressed(&gamepads, GamepadButton::Select) {
+        next_state.set(GameState::MainMenu);
+    }
+}
+
+// A restart goes through RoundOver so OnEnter(Playing) rebuilds the arena
+fn restart_round(
+    mut reset_reader: MessageReader<ResetGameEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
 ) {
     if reset_reader.is_empty() {
         return;
     }
     reset_reader.clear();
+    next_state.set(GameState::RoundOver);
+}
 
-    // Despawn everything that belongs to the game
-    for entity in &game_entities {
-        commands.entity(entity).despawn();
+fn record_kills(mut destroyed: MessageReader<ShipDestroyed>, mut stats: ResMut<MatchStats>) {
+    for event in destroyed.read() {
+        if let Some(killer) = event.killer {
+            stats.players[killer.index()].ships_destroyed += 1;
+        }
+    }
+}
+
+fn check_round_end(
+    players: Query<&PlayerId, With<Player>>,
+    mut score: ResMut<MatchScore>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let alive: Vec<PlayerId> = players.iter().copied().collect();
+    if alive.len() >= 2 {
+        return;
     }
 
-    // Recreate initial state
-    setup(commands, asset_server,meshes,materials); 
+    // Last ship flying takes the round, a double knockout just replays it
+    score.round_winner = alive.first().copied();
+    if let Some(id) = score.round_winner {
+        score.wins[id.index()] += 1;
+        if score.wins(id) >= score.wins_needed() {
+            score.match_winner = Some(id);
+            next_state.set(GameState::MatchOver);
+            return;
+        }
+    }
+    score.round += 1;
+    // Straight to RoundOver rather than via a ResetGameEvent, so the round is
+    // left before anything else can be destroyed and score it again
+    next_state.set(GameState::RoundOver);
 }
 
 #[derive(Component)]
@@ -181,18 +815,29 @@ struct HealthPoint {
     blue: bool,
 }
 
-#[derive(Component, Clone, Copy, PartialEq, Eq)]
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
 enum PlayerId {
     One,
     Two,
 }
 
+impl PlayerId {
+    fn index(self) -> usize {
+        match self {
+            PlayerId::One => 0,
+            PlayerId::Two => 1,
+        }
+    }
+}
+
 #[derive(Component)]
 struct Health {
     hp: i32,
 }
 
+
 #[derive(Component)]
+#[require(Interpolated)]
 struct Player {
     movement_speed: f32,
     rotation_speed: f32,
@@ -202,9 +847,14 @@ struct Player {
 }
 
 #[derive(Component)]
+#[require(Interpolated)]
 struct Asteroid {
     velocity: Vec2,
+    /// Rough size of the outline, for bouncing off other asteroids
     radius: f32,
+    size: AsteroidSize,
+    /// Radians per second
+    spin: f32,
 }
 
 #[derive(Resource, Default)]
@@ -213,8 +863,40 @@ struct AsteroidSpawner {
 }
 
 #[derive(Component)]
+#[require(ShieldRecharge, ParryCooldown)]
 struct ShieldHealth {
     shp: f32,
+    max: f32,
+    /// Knocked down to nothing and locked out until its `ShieldRecharge` finishes
+    broken: bool,
+}
+
+impl ShieldHealth {
+    fn new(max: f32) -> Self {
+        Self {
+            shp: max,
+            max,
+            broken: false,
+        }
+    }
+
+    fn restore(&mut self, amount: f32) {
+        self.shp = (self.shp + amount).min(self.max);
+    }
+}
+
+/// Runs from when the shield was last up or knocked out, no regen until it
+/// finishes. Kept apart from `ShieldHealth` so ticking it doesn't redraw the bars.
+#[derive(Component, Default)]
+struct ShieldRecharge {
+    timer: Timer,
+}
+
+impl ShieldRecharge {
+    /// Holds off regen for `seconds` from now
+    fn rest(&mut self, seconds: f32) {
+        self.timer = Timer::from_seconds(seconds, TimerMode::Once);
+    }
 }
 
 #[derive(Component)]
@@ -223,131 +905,310 @@ struct ShieldPoint {
     blue: bool,
 }
 
+/// A raised shield bubble, parented to its ship
 #[derive(Component)]
-struct Shield; 
+struct Shield {
+    /// Seconds since it went up
+    age: f32,
+    /// Seconds after going up that shots touching it are parried, zero if this
+    /// raise came too soon after the last parry window to get one
+    parry_window: f32,
+}
+
+impl Shield {
+    fn new(parry_window: f32) -> Self {
+        Self { age: 0.0, parry_window }
+    }
 
+    /// Whether a shot that touched it `ago` seconds before now did so inside
+    /// the parry window. Touching it on the tick it went up counts.
+    fn parries(&self, ago: f32) -> bool {
+        self.parry_window > 0.0 && self.age - ago <= self.parry_window
+    }
+}
+
+/// Runs from when a shield last got a parry window; raising it again before
+/// this finishes gives a plain shield, so tapping can't parry everything
+#[derive(Component, Default)]
+struct ParryCooldown {
+    timer: Timer,
+}
+
+/// Opacity of a shield bubble at rest
+const SHIELD_ALPHA: f32 = 0.4;
+
+/// Ring thrown out where a shot was parried, growing and fading until the timer runs out
+#[derive(Component)]
+struct ParryFlash {
+    timer: Timer,
+}
+
+/// A shield taking a hit, flashing until the timer runs out
 #[derive(Component)]
+struct ShieldFlicker {
+    timer: Timer,
+}
+
+impl ShieldFlicker {
+    fn new() -> Self {
+        Self { timer: Timer::from_seconds(0.25, TimerMode::Once) }
+    }
+}
+
+#[derive(Component)]
+#[require(Interpolated)]
 struct Projectile {
     velocity: Vec2,
     radius: f32,
     owner: PlayerId,
+    damage: i32,
+    lifetime: Timer,
+    /// Distance left before it fizzles out
+    range: f32,
+    /// Ground covered last tick, which is checked end to end for hits
+    sweep: Vec2,
+    /// How far the body runs ahead of and behind its centre beyond `radius`,
+    /// zero for round shots
+    half_length: f32,
+}
+
+impl Projectile {
+    /// How far from where it is now last tick's path reaches
+    fn extent(&self) -> f32 {
+        self.sweep.length() + self.half_length
+    }
+
+    /// Last tick's path in the frame of a target that is now `offset` from the
+    /// shot and moved by `target_step`, as where it began and the sweep from there.
+    /// A long shot is run from its tail to its nose, which covers its whole body.
+    fn relative_path(&self, offset: Vec2, target_step: Vec2) -> (Vec2, Vec2) {
+        let body = self.velocity.normalize_or_zero() * self.half_length;
+        let sweep = self.sweep + 2.0 * body - target_step;
+        (offset + body - sweep, sweep)
+    }
+
+    /// Fraction of last tick's move at which this shot, now at `position`, came
+    /// within `reach` of a target now at `target` that moved by `target_step`
+    fn hit_time(&self, arena: &Arena, position: Vec2, target: Vec2, target_step: Vec2, reach: f32) -> Option<f32> {
+        // Worked in the target's frame, so its own motion counts too
+        let (start, sweep) = self.relative_path(arena.delta(target, position), target_step);
+        sweep_entry(-start, sweep, reach)
+    }
+
+    /// Where this shot was relative to the target at fraction `t` of last tick,
+    /// taking the same positions as `hit_time`
+    fn offset_at(&self, arena: &Arena, position: Vec2, target: Vec2, target_step: Vec2, t: f32) -> Vec2 {
+        let (start, sweep) = self.relative_path(arena.delta(target, position), target_step);
+        start + sweep * t
+    }
 }
 
 #[derive(Resource)]
 struct Sounds {
     laser: Handle<AudioSource>,
     damage: Handle<AudioSource>,
+    empty: Handle<AudioSource>,
+    reload: Handle<AudioSource>,
+    parry: Handle<AudioSource>,
+}
+
+/// What a ship's pilot is asking for this tick, written by whatever drives the ship
+#[derive(Component, Default)]
+struct ShipInput {
+    /// -1.0 (clockwise) to 1.0 (counter clockwise)
+    rotate: f32,
+    /// Direction to turn and face, takes over from `rotate` when set
+    aim: Option<Vec2>,
+    /// 0.0 to 1.0
+    thrust: f32,
+    shield: bool,
+    /// Fire is being held down, for weapons that charge
+    fire_held: bool,
+    /// Latched until the fixed step consumes them
+    fire: bool,
+    warp: bool,
+    reload: bool,
 }
 
-//implementation 2
 #[derive(Component)]
-struct AssignedController {
-    gp: Option<Entity>,
+struct Weapon {
+    magazine: u32,
+    ammo: u32,
+    reload: Timer,
+    reloading: bool,
+    /// Start reloading by itself once the magazine runs dry
+    auto_reload: bool,
 }
 
-#[derive(Resource, Default)]
-struct PlayerControllers {
-    p1: Option<Entity>,
-    p2: Option<Entity>,
+impl Weapon {
+    fn new(loadout: &Loadout) -> Self {
+        Self {
+            magazine: loadout.magazine,
+            ammo: loadout.magazine,
+            reload: Timer::from_seconds(loadout.reload_time, TimerMode::Once),
+            reloading: false,
+            auto_reload: loadout.auto_reload,
+        }
+    }
+
+    /// Returns false if already reloading or the magazine is full
+    fn start_reload(&mut self) -> bool {
+        if self.reloading || self.ammo == self.magazine {
+            return false;
+        }
+        self.reloading = true;
+        self.reload.reset();
+        true
+    }
 }
 
+/// Rounds left in a ship's magazine, shown beside its health blocks
+#[derive(Component)]
+struct AmmoText(PlayerId);
+
 #[derive(Component)]
 struct WarpCooldown {
     timer: Timer,
-}
-
-fn handle_connection(
-    mut events: MessageReader<GamepadConnectionEvent>,
-    mut controllers: ResMut<PlayerControllers>,
-) {
-    for event in events.read() {
-        match &event.connection {
-            GamepadConnection::Connected { name, vendor_id, product_id } => {
-                println!("Connected");
-                if controllers.p1.is_none() {
-                    controllers.p1 = Some(event.gamepad);
-                } else if controllers.p2.is_none() {
-                    controllers.p2 = Some(event.gamepad);
-                }                                
-            }
-            GamepadConnection::Disconnected => {
-                println!("Disconnected");
-            }
-        }
-    }
+    distance: f32,
 }
 
 fn load_audio_assets(mut commands: Commands, asset_server: Res<AssetServer>) {
     let laser_sound = asset_server.load("sounds/laser.ogg"); 
     let damage_sounds = asset_server.load("sounds/damage.ogg"); //Hew moaning
+    let empty_sound = asset_server.load("sounds/empty.wav");
+    let reload_sound = asset_server.load("sounds/reload.wav");
+    let parry_sound = asset_server.load("sounds/parry.wav");
     commands.insert_resource(
         Sounds { 
             laser: laser_sound,
             damage: damage_sounds,
+            empty: empty_sound,
+            reload: reload_sound,
+            parry: parry_sound,
         });
 }
 
 fn setup(
             mut commands: Commands, 
-            asset_server: Res<AssetServer>,
             mut meshes: ResMut<Assets<Mesh>>,
             mut materials: ResMut<Assets<ColorMaterial>>,
+            score: Res<MatchScore>,
+            roster: Res<ShipRoster>,
+            defs: Res<Assets<ShipDef>>,
+            choices: Res<ShipChoices>,
          ) {
-    let blueship = asset_server.load("starblue.png");
-    let redship = asset_server.load("starred.png");
+    // Ship select only lets a match start once both picks have loaded
+    let (Some(red), Some(blue)) = (
+        roster.get(choices.picks[PlayerId::One.index()], &defs),
+        roster.get(choices.picks[PlayerId::Two.index()], &defs),
+    ) else {
+        warn!("Ship definitions not loaded, cannot start the round");
+        return;
+    };
 
-    let max_health = 500;
+    // Round counter along the top of the screen
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        GameEntity,
+        children![(
+            Text::new(format!(
+                "Round {}  |  {}  |  Best of {}",
+                score.round,
+                score_line(&score),
+                score.best_of,
+            )),
+            TextFont { font_size: 20.0, ..default() },
+            TextColor(Color::WHITE),
+        )],
+    ));
 
     // Player 1
     commands.spawn((
-        Sprite::from_image(redship),
+        Sprite::from_image(red.sprites[0].clone()),
         Player {
-            movement_speed: 500.0,
-            rotation_speed: f32::to_radians(300.0),
+            movement_speed: red.movement_speed,
+            rotation_speed: red.rotation_speed,
             velocity: Vec2::ZERO,
-            radius: 17.0,
+            radius: red.radius,
             color: 1,
         },
         PlayerId::One,
-        Health { hp: max_health },
+        Health { hp: red.max_health },
+        Armor(red.armor),
+        Wraps { radius: red.radius },
         Transform::from_xyz(-300.0, 0.0, 0.0),
         WarpCooldown{
-            timer: Timer::from_seconds(5.0, TimerMode::Once),
+            timer: Timer::from_seconds(red.warp_cooldown, TimerMode::Once),
+            distance: red.warp_distance,
         },
         GameEntity,
-        ShieldHealth {shp: 500.0},
+        ShieldHealth::new(red.max_shield),
+        red.shield.clone(),
+        red.weapon.clone(),
+        Weapon::new(&red.weapon),
+        ShipInput::default(),
     ));
 
     // Player 2
     commands.spawn((
-        Sprite::from_image(blueship),
+        Sprite::from_image(blue.sprites[1].clone()),
         Player {
-            movement_speed: 500.0,
-            rotation_speed: f32::to_radians(300.0),
+            movement_speed: blue.movement_speed,
+            rotation_speed: blue.rotation_speed,
             velocity: Vec2::ZERO,
-            radius: 17.0,
+            radius: blue.radius,
             color: 2,
         },
         PlayerId::Two,
-        Health { hp: max_health },
+        Health { hp: blue.max_health },
+        Armor(blue.armor),
+        Wraps { radius: blue.radius },
         Transform::from_xyz(300.0, 0.0, 0.0),
         WarpCooldown{
-            timer: Timer::from_seconds(5.0, TimerMode::Once),
+            timer: Timer::from_seconds(blue.warp_cooldown, TimerMode::Once),
+            distance: blue.warp_distance,
         },
         GameEntity,
-        ShieldHealth {shp: 500.0},
+        ShieldHealth::new(blue.max_shield),
+        blue.shield.clone(),
+        blue.weapon.clone(),
+        Weapon::new(&blue.weapon),
+        ShipInput::default(),
+    ));
+    commands.spawn((
+        Text2d::new(format!("{}", blue.weapon.magazine)),
+        TextFont { font_size: 20.0, ..default() },
+        TextColor(Color::srgb(0.6, 0.6, 1.0)),
+        Transform::from_xyz(555., 300., 0.0),
+        AmmoText(PlayerId::Two),
+        GameEntity,
+    ));
+    commands.spawn((
+        Text2d::new(format!("{}", red.weapon.magazine)),
+        TextFont { font_size: 20.0, ..default() },
+        TextColor(Color::srgb(1.0, 0.6, 0.6)),
+        Transform::from_xyz(-555., 300., 0.0),
+        AmmoText(PlayerId::One),
+        GameEntity,
     ));
     let mut space = 300.0;
     commands.spawn((
-        Mesh2d(meshes.add(Rectangle::new(100.,20.0))),
+        Mesh2d(meshes.add(Rectangle::new(blue.max_shield / 5.0, 20.0))),
         MeshMaterial2d(materials.add(Color::srgba(0.2,0.2,1.0,0.5))),
         Transform::from_xyz(space+87., 270., 0.0),
         ShieldPoint {
                 red: false,
                 blue: true,
             },
+        GameEntity,
     ));
-    for _ in 0..((max_health+1)/100) {
+    for _ in 0..((blue.max_health+1)/100) {
         space += 25.;
         let shape = meshes.add(Rectangle::new(20.0,20.0));
         commands.spawn((
@@ -363,15 +1224,16 @@ fn setup(
     }
     space = -500.0;
        commands.spawn((
-        Mesh2d(meshes.add(Rectangle::new(100.0,20.0))),
+        Mesh2d(meshes.add(Rectangle::new(red.max_shield / 5.0, 20.0))),
         MeshMaterial2d(materials.add(Color::srgba(1.0,0.2,0.2,0.5))),
         Transform::from_xyz(space+87., 270., 0.0),
         ShieldPoint {
                 red: true,
                 blue: false,
             },
+        GameEntity,
     ));
-    for _ in 0..((max_health+1)/100) {
+    for _ in 0..((red.max_health+1)/100) {
         space += 25.;
         let shape = meshes.add(Rectangle::new(20.0,20.0));
         commands.spawn((
@@ -387,233 +1249,192 @@ fn setup(
     }
 }
 
+/// Stretches each shield bar to the points left, rather than rebuilding it
 fn update_shield_ui(
-    mut commands: Commands,
-    shp_ui: Query<(Entity, &ShieldPoint)>,
+    mut shp_ui: Query<(&ShieldPoint, &mut Transform, &MeshMaterial2d<ColorMaterial>)>,
     qshield: Query<(&ShieldHealth, &Player), Changed<ShieldHealth>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,    
-    ) {
-    let hp_blocks: Vec<_> = shp_ui.iter().collect();
-
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
     for (hp, player) in &qshield {
-        let size = 100. - (100. - (hp.shp/5.));
-        if player.color == 2 {
-            // Blue player
-            for (entity, block) in hp_blocks.iter() {
-                let space = 300.0;
-                if block.blue {
-                    commands.entity(*entity).despawn();
-                    commands.spawn((
-                        Mesh2d(meshes.add(Rectangle::new(size,20.0))),
-                        MeshMaterial2d(materials.add(Color::srgba(0.2,0.2,1.0,0.5))),
-                        Transform::from_xyz(space+87., 270., 0.0),
-                        ShieldPoint {
-                            red: false,
-                            blue: true,
-                        },
-                        GameEntity,
-                    ));
-                    break;
-                }
+        // Bars are built at full width
+        let fill = if hp.max > 0. { (hp.shp / hp.max).clamp(0., 1.) } else { 0. };
+        // Faded out while a broken shield is locked out
+        let alpha = if hp.broken { 0.15 } else { 0.5 };
+        for (block, mut transform, material) in &mut shp_ui {
+            let ours = if player.color == 2 { block.blue } else { block.red };
+            if !ours {
+                continue;
             }
-        } 
-        if player.color == 1 {
-            // Red player
-            for (entity, block) in hp_blocks.iter() {
-                let space = -500.0;
-                if block.red {
-                    commands.entity(*entity).despawn();
-                    commands.spawn((
-                        Mesh2d(meshes.add(Rectangle::new(size,20.0))),
-                        MeshMaterial2d(materials.add(Color::srgba(1.0,0.2,0.2,0.5))),
-                        Transform::from_xyz(space+87., 270., 0.0),
-                        ShieldPoint {
-                            red: true,
-                            blue: false,
-                        },
-                        GameEntity,
-                    ));
-                    break;
-                }
+            transform.scale.x = fill;
+            // Looked at first, as borrowing it mutably counts as a change
+            if materials.get(&material.0).is_some_and(|material| material.color.alpha() != alpha)
+                && let Some(material) = materials.get_mut(&material.0)
+            {
+                material.color.set_alpha(alpha);
             }
         }
     }
 }
 
-fn update_health_ui(
-    mut commands: Commands,
-    hp_ui: Query<(Entity, &HealthPoint)>,
-    qhealth: Query<(&Health, &Player), Changed<Health>>,
-    audio: Res<Audio>,
-    sounds: Res<Sounds>,
+fn update_ammo_ui(
+    weapons: Query<(&PlayerId, &Weapon, Option<&EquippedWeapon>)>,
+    mut texts: Query<(&AmmoText, &mut Text2d)>,
 ) {
-    let hp_blocks: Vec<_> = hp_ui.iter().collect();
-
-    for (hp, player) in &qhealth {
-        if player.color == 2 {
-            // Blue player
-            if (hp.hp as f32 / 100.) - (hp.hp as f32 / 100.) != (hp.hp % 100) as f32 {
-                continue
-            }
-            for (entity, block) in hp_blocks.iter() {
-                if block.blue {
-                    commands.entity(*entity).despawn();
-                    audio.play(sounds.damage.clone());
-                    break;
-                }
+    for (id, weapon, equipped) in &weapons {
+        for (ammo, mut text) in &mut texts {
+            if ammo.0 != *id {
+                continue;
             }
-        } 
-        if player.color == 1{
-            // Red player
-            if (hp.hp as f32 / 100.) - (hp.hp as f32 / 100.) != (hp.hp % 100) as f32 {
-                continue
-            }
-            for (entity, block) in hp_blocks.iter() {
-                if block.red {
-                    commands.entity(*entity).despawn();
-                    audio.play(sounds.damage.clone());
-                    break;
-                }
+            let label = if let Some(equipped) = equipped {
+                format!("{} {}", equipped.kind.label(), equipped.uses)
+            } else if weapon.reloading {
+                "...".to_string()
+            } else {
+                format!("{}", weapon.ammo)
+            };
+            if text.0 != label {
+                text.0 = label;
             }
         }
     }
 }
 
-
-fn gamepad_for_player(
-    controllers: &PlayerControllers,
-    id: PlayerId,
-) -> Option<Entity> {
-    match id {
-        PlayerId::One => controllers.p1,
-        PlayerId::Two => controllers.p2,
+/// A destroyed ship never gets its last health change drawn, so empty its row
+fn clear_destroyed_health_ui(
+    mut commands: Commands,
+    mut destroyed: MessageReader<ShipDestroyed>,
+    hp_ui: Query<(Entity, &HealthPoint)>,
+) {
+    for event in destroyed.read() {
+        let blue = event.ship == PlayerId::Two;
+        for (entity, block) in &hp_ui {
+            if (blue && block.blue) || (!blue && block.red) {
+                commands.entity(entity).despawn();
+            }
+        }
     }
 }
 
-fn rotation_controller(
-    time: Res<Time>,
-    controllers: Res<PlayerControllers>,
-    gamepads: Query<(Entity, &Gamepad)>,
-    mut query: Query<(&PlayerId, &Player, &mut Transform)>,
+fn update_health_ui(
+    mut commands: Commands,
+    hp_ui: Query<(Entity, &HealthPoint)>,
+    qhealth: Query<(&Health, &Player), Changed<Health>>,
+    audio: Res<Audio>,
+    sounds: Res<Sounds>,
 ) {
-    for (id, ship, mut transform) in &mut query {
-        // Skip if no gamepad assigned
-        
-        let Some(gamepad) = gamepad_for_player(&controllers, *id) else {
-            continue;
-        };
-
-        let mut rotation_factor = 0.0;
-        for (e, gp) in &gamepads {
-            if Some(e) == Some(gamepad) {
-                if gp.pressed(GamepadButton::DPadLeft) {
-                    rotation_factor += 1.0;
-                }
-                if gp.pressed(GamepadButton::DPadRight) {
-                    rotation_factor -= 1.0;
-                }                
-            }
-                        
+    for (hp, player) in &qhealth {
+        // One block per 100 hp, a partly lost block still shows
+        let remaining = (hp.hp.max(0) + 99) / 100;
+        let blocks: Vec<_> = hp_ui
+            .iter()
+            .filter(|(_, block)| if player.color == 2 { block.blue } else { block.red })
+            .collect();
+        let lost = blocks.len().saturating_sub(remaining as usize);
+        for (entity, _) in blocks.iter().take(lost) {
+            commands.entity(*entity).despawn();
+        }
+        if lost > 0 {
+            audio.play(sounds.damage.clone());
         }
-        // Apply rotation
-        transform.rotate_z(rotation_factor * ship.rotation_speed * time.delta_secs());
     }
 }
 
-
-
-
-fn rotation(
-    time: Res<Time>,
-    keyboard: Res<ButtonInput<KeyCode>>,
-    mut query: Query<(&PlayerId, &Player, &mut Transform)>,
+fn collect_ship_input(
+    actions: PlayerActions,
+    mut query: Query<(&PlayerId, &mut ShipInput)>,
 ) {
-    for (id, ship, mut transform) in &mut query {
-        let mut rotation_factor = 0.0;
-
-        match id {
-            PlayerId::One => {
-                if keyboard.pressed(KeyCode::KeyA) {
-                    rotation_factor += 1.0;
-                }
-                if keyboard.pressed(KeyCode::KeyD) {
-                    rotation_factor -= 1.0;
-                }
-            }
-            PlayerId::Two => {
-                if keyboard.pressed(KeyCode::ArrowLeft) {
-                    rotation_factor += 1.0;
+    for (id, mut input) in &mut query {
+        let mut rotate = 0.0;
+        if actions.pressed(*id, |b| &b.rotate_left) {
+            rotate += 1.0;
+        }
+        if actions.pressed(*id, |b| &b.rotate_right) {
+            rotate -= 1.0;
+        }
+        let mut thrust = if actions.pressed(*id, |b| &b.thrust) { 1.0 } else { 0.0 };
+        let mut aim = None;
+
+        if let Some(gp) = actions.gamepad(*id) {
+            let analog = &actions.bindings(*id).analog;
+            let stick = gp.left_stick();
+            let strength = AnalogSettings::shape(stick.length(), analog.stick_dead_zone, analog.stick_curve);
+            match analog.steering {
+                SteeringMode::Tank => {
+                    let turn = AnalogSettings::shape(stick.x, analog.stick_dead_zone, analog.stick_curve);
+                    if turn != 0.0 {
+                        rotate = -turn;
+                    }
                 }
-                if keyboard.pressed(KeyCode::ArrowRight) {
-                    rotation_factor -= 1.0;
+                SteeringMode::PointToFace => {
+                    if strength > 0.0 {
+                        aim = Some(stick.normalize());
+                    }
                 }
             }
+            let trigger = gp.get(GamepadButton::RightTrigger2).unwrap_or(0.0);
+            thrust = f32::max(
+                thrust,
+                AnalogSettings::shape(trigger, analog.trigger_dead_zone, analog.trigger_curve),
+            );
         }
 
-        transform.rotate_z(rotation_factor * ship.rotation_speed * time.delta_secs());
+        input.rotate = rotate.clamp(-1.0, 1.0);
+        input.aim = aim;
+        input.thrust = thrust;
+        input.shield = actions.pressed(*id, |b| &b.shield);
+        input.fire_held = actions.pressed(*id, |b| &b.fire);
+
+        if actions.just_pressed(*id, |b| &b.fire) {
+            input.fire = true;
+        }
+        if actions.just_pressed(*id, |b| &b.warp) {
+            input.warp = true;
+        }
+        if actions.just_pressed(*id, |b| &b.reload) {
+            input.reload = true;
+        }
     }
 }
 
-
-fn thrust_controller(
+fn rotation(
     time: Res<Time>,
-    controllers: Res<PlayerControllers>,
-    gamepads: Query<(Entity, &Gamepad)>,
-    mut controller_query: Query<(&mut Player, &PlayerId, &Transform)>,
+    mut query: Query<(&ShipInput, &Player, &mut Transform)>,
 ) {
-    for (mut ship, id, transform) in &mut controller_query {
-        // Get assigned gamepad for this PlayerId
-        let Some(gamepad) = gamepad_for_player(&controllers, *id) else {
-            continue;
-        };
-
-        let mut pressed = false;
-        for (e, gp) in gamepads {
-            if Some(e) == Some(gamepad) {
-                if gp.pressed(GamepadButton::DPadUp) {
-                    pressed = true;
-                    break;
-                }
+    for (input, ship, mut transform) in &mut query {
+        let max_turn = ship.rotation_speed * time.delta_secs();
+        match input.aim {
+            // Turn toward the stick, no faster than the ship's turn rate
+            Some(aim) => {
+                let forward = (transform.rotation * Vec3::Y).truncate();
+                let turn = forward.angle_to(aim).clamp(-max_turn, max_turn);
+                transform.rotate_z(turn);
             }
-        }
-
-        // If the button is pressed
-        if pressed {
-            let speed = ship.movement_speed;
-            let forward = (transform.rotation * Vec3::Y).truncate();
-            ship.velocity += forward * speed * time.delta_secs();
+            None => transform.rotate_z(input.rotate * max_turn),
         }
     }
 }
 
-
 fn thrust(
     time: Res<Time>,
-    keyboard: Res<ButtonInput<KeyCode>>,
-    mut query: Query<(&PlayerId, &mut Player, &Transform)>,
+    mut query: Query<(&ShipInput, &mut Player, &Transform)>,
 ) {
-    for (id, mut ship, transform) in &mut query {
-        let forward = (transform.rotation * Vec3::Y).truncate();
-        let speed = ship.movement_speed;
-
-        let pressing = match id {
-            PlayerId::One => keyboard.pressed(KeyCode::KeyW),
-            PlayerId::Two => keyboard.pressed(KeyCode::ArrowUp),
-        };
-
-        if pressing {
-            ship.velocity += forward * speed * time.delta_secs();
+    for (input, mut ship, transform) in &mut query {
+        if input.thrust > 0.0 {
+            let forward = (transform.rotation * Vec3::Y).truncate();
+            let speed = ship.movement_speed;
+            ship.velocity += forward * speed * input.thrust * time.delta_secs();
         }
     }
 }
 
 fn move_player(
     time: Res<Time>,
+    tuning: Res<GameTuning>,
     mut query: Query<(&mut Player, &mut Transform)>,
+    arena: Res<Arena>,
 ) {
     for (mut ship, mut transform) in &mut query {
-        let friction = 0.8;
+        let friction = tuning.friction;
         let vel = ship.velocity;
         ship.velocity = vel - vel * friction * time.delta_secs();
 
@@ -621,282 +1442,270 @@ fn move_player(
         transform.translation.x += delta.x;
         transform.translation.y += delta.y;
 
-        let extents = Vec3::from((BOUNDS / 2.0, 0.0));
-        transform.translation = transform.translation.min(extents).max(-extents);
+        transform.translation = arena.confine(transform.translation);
     }
 }
 
 
+/// Raises and drops the shield bubble as the pilot holds the button, drains it
+/// while up and recharges it once it has been left alone for a while
 fn shield_system(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
-    keyboard: Res<ButtonInput<KeyCode>>,
-    mut player_query: Query<(Entity, &PlayerId, &mut ShieldHealth, Option<&Children>), Without<Shield>>,
-    shielded_query: Query<&ChildOf, With<Shield>>,
+    mut player_query: Query<(
+        Entity,
+        &ShipInput,
+        &ShieldStats,
+        &mut ShieldHealth,
+        &mut ShieldRecharge,
+        &mut ParryCooldown,
+        Option<&Children>,
+    )>,
+    mut shielded_query: Query<&mut Shield>,
+    time: Res<Time>,
+    tuning: Res<GameTuning>,
 ) {
-    for (entity, id, mut shield, children) in &mut player_query {
-        let pressed = match id {
-            PlayerId::One => keyboard.pressed(KeyCode::KeyQ),
-            PlayerId::Two => keyboard.pressed(KeyCode::ShiftRight),
-        };
+    // Only touches `ShieldHealth` when something about it changes, so the bars
+    // are only rebuilt then
+    for (entity, input, stats, mut shield, mut recharge, mut parry, children) in &mut player_query {
+        let raised = children.and_then(|children| children.iter().find(|&child| shielded_query.contains(child)));
+
+        recharge.timer.tick(time.delta());
+        parry.timer.tick(time.delta());
+        if shield.broken && recharge.timer.is_finished() {
+            shield.broken = false;
+        }
 
-        let has_shield = children.map_or(false, |children| {
-            children.iter().any(|child| shielded_query.get(child).is_ok())
-        });
+        // Run dry, either from holding it up or from soaking up hits
+        if raised.is_some() && shield.shp <= 0. && !shield.broken {
+            shield.shp = 0.;
+            shield.broken = true;
+            recharge.rest(stats.broken_lockout);
+        }
 
-        if shield.shp <= 0. {
-            if let Some(children) = children {
-                for child in children.iter() {
-                    if shielded_query.get(child).is_ok() {
-                        commands.entity(child).despawn();
-                    }
+        let up = input.shield && !shield.broken && shield.shp > 0.;
+        match (up, raised) {
+            (true, Some(bubble)) => {
+                shield.shp = (shield.shp - stats.drain * time.delta_secs()).max(0.);
+                if let Ok(mut bubble) = shielded_query.get_mut(bubble) {
+                    bubble.age += time.delta_secs();
                 }
             }
-            continue;           
-        }
-        // PRESSING — ensure shield exists
-        if pressed {
-            // Skip if shield already exists
-            if has_shield {
-                if shield.shp >= 0. {
-                    shield.shp -= 1.0;               
-                }
-                continue;
+            (true, None) => {
+                let shield_mesh = meshes.add(Circle::new(tuning.shield_radius).mesh());
+                let shield_material = materials.add(Color::srgba(0.3, 0.7, 1.0, SHIELD_ALPHA));
+
+                // Only a raise after the last window and its cooldown have run out can parry
+                let parry_window = if parry.timer.is_finished() {
+                    parry.timer = Timer::from_seconds(tuning.parry_window + tuning.parry_cooldown, TimerMode::Once);
+                    tuning.parry_window
+                } else {
+                    0.0
+                };
+
+                // Spawn shield as child of player
+                commands.entity(entity).with_children(|parent| {
+                    parent.spawn((
+                        Shield::new(parry_window),
+                        Mesh2d(shield_mesh),
+                        MeshMaterial2d(shield_material),
+                        Transform::default(),
+                        GlobalTransform::default(),
+                    ));
+                });
             }
-                       
-            let shield_mesh = meshes.add(Circle::new(40.0).mesh());
-            let shield_material = materials.add(Color::srgba(0.3, 0.7, 1.0, 0.4));
-
-            // Spawn shield as child of player
-            commands.entity(entity).with_children(|parent| {
-                parent.spawn((
-                    Shield,
-                    Mesh2d(shield_mesh),
-                    MeshMaterial2d(shield_material),
-                    Transform::default(),
-                    GlobalTransform::default(),
-                ));
-            });
-        } else {
-            // NOT PRESSING — remove shield child if exists
-            if let Some(children) = children {
-                for child in children.iter() {
-                    if shielded_query.get(child).is_ok() {
-                        commands.entity(child).despawn();
-                    }
+            (false, Some(bubble)) => {
+                commands.entity(bubble).despawn();
+            }
+            (false, None) => {
+                if !shield.broken && recharge.timer.is_finished() && shield.shp < shield.max {
+                    shield.restore(stats.regen * time.delta_secs());
                 }
             }
         }
+        if up {
+            recharge.rest(stats.regen_delay);
+        }
     }
 }
 
 
-fn shield_system_controller(
+/// Stops shots at the edge of a raised shield, before they can reach the hull.
+/// A shield raised just before the shot arrives throws it back, now belonging
+/// to the defender; otherwise what the shield soaks up is settled in `apply_damage`.
+fn projectile_shield_collision(
     mut commands: Commands,
+    mut projectile_query: Query<(Entity, &mut Transform, &mut Projectile)>,
+    player_query: Query<(Entity, &Transform, &Player, &PlayerId, &Children), Without<Projectile>>,
+    shields: Query<&Shield>,
+    mut damage: MessageWriter<DamageEvent>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
-    gamepads: Query<(Entity, &Gamepad)>,
-    controllers: Res<PlayerControllers>,     
-    mut player_query: Query<(Entity, &PlayerId, &mut ShieldHealth, Option<&Children>), Without<Shield>>,
-    shielded_query: Query<&ChildOf, With<Shield>>,
+    audio: Res<Audio>,
+    sounds: Res<Sounds>,
+    mut stats: ResMut<MatchStats>,
+    grid: Res<BroadPhase>,
+    time: Res<Time>,
+    tuning: Res<GameTuning>,
+    arena: Res<Arena>,
 ) {
-    for (entity, id, mut shield, children) in &mut player_query {
-        let mut pressed = false;
-        let Some(gamepad) = gamepad_for_player(&controllers, *id) else {
+    for (proj_entity, mut proj_tf, mut proj) in &mut projectile_query {
+        let proj_pos = proj_tf.translation.truncate();
+        let reach = tuning.shield_radius + proj.radius;
+
+        // Whichever raised shield the shot reached first along its path
+        let hit = grid
+            .near(proj_pos, proj.extent() + reach)
+            .into_iter()
+            .filter_map(|candidate| player_query.get(candidate).ok())
+            .filter(|(_, _, _, player_id, _)| proj.owner != **player_id) // don't hit yourself
+            .filter_map(|(player_entity, player_tf, player, player_id, children)| {
+                let bubble = children.iter().find(|&child| shields.contains(child))?;
+                let player_pos = player_tf.translation.truncate();
+                let step = player.velocity * time.delta_secs();
+                proj.hit_time(&arena, proj_pos, player_pos, step, reach)
+                    .map(|t| (t, player_entity, *player_id, player_pos, step, bubble))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+        let Some((t, player_entity, player_id, player_pos, step, bubble)) = hit else {
             continue;
         };
-        // Match assigned controller
-        for (e, gp) in &gamepads {
-            if Some(e) == Some(gamepad) {
-                // NORTH = shield button
-                if gp.pressed(GamepadButton::North) {
-                    pressed = true;
-                }
-            }
-        }
-
-        let has_shield = children.map_or(false, |children| {
-            children.iter().any(|child| shielded_query.get(child).is_ok())
-        });
-
 
-        if shield.shp <= 0. {
-            if let Some(children) = children {
-                for child in children.iter() {
-                    if shielded_query.get(child).is_ok() {
-                        commands.entity(child).despawn();
-                    }
-                }
-            }
-            continue;           
-        }        
-        // PRESSING — ensure shield exists
-        if pressed {
-                        
-            if has_shield {
-                if shield.shp >= 0. {
-                    shield.shp -= 1.0;               
-                }
-                continue;
+        // When along last tick's path it touched, so a window that closed part
+        // way through the tick still catches shots that arrived before then
+        let ago = (1.0 - t) * time.delta_secs();
+        if shields.get(bubble).is_ok_and(|shield| shield.parries(ago)) {
+            // Bounced off the bubble where it touched, and put back outside it
+            let normal = proj
+                .offset_at(&arena, proj_pos, player_pos, step, t)
+                .normalize_or(-proj.velocity.normalize_or_zero());
+            if proj.velocity.dot(normal) < 0.0 {
+                proj.velocity = proj.velocity.reflect(normal);
             }
-            
-            
-            let shield_mesh = meshes.add(Circle::new(40.0).mesh());
-            let shield_material = materials.add(Color::srgba(0.3, 0.7, 1.0, 0.4));
-
-            commands.entity(entity).with_children(|parent| {
-                parent.spawn((
-                    Shield,
-                    Mesh2d(shield_mesh),
-                    MeshMaterial2d(shield_material),
-                    Transform::default(),
-                    GlobalTransform::default(),
-                ));
-            });
-        }
-        // NOT PRESSING — remove shield child if it exists
-        else {
-            if let Some(children) = children {
-                for child in children.iter() {
-                    if shielded_query.get(child).is_ok() {
-                        commands.entity(child).despawn();
-                    }
-                }
+            let contact = player_pos + normal * reach;
+            proj_tf.translation = arena.confine(contact.extend(proj_tf.translation.z));
+            if proj.velocity != Vec2::ZERO {
+                proj_tf.rotation = Quat::from_rotation_arc_2d(Vec2::Y, proj.velocity.normalize());
             }
+            proj.owner = player_id;
+            proj.sweep = Vec2::ZERO;
+            commands
+                .entity(proj_entity)
+                .insert(MeshMaterial2d(materials.add(projectile_color_for(player_id))));
+
+            commands.entity(bubble).insert(ShieldFlicker::new());
+            commands.spawn((
+                Mesh2d(meshes.add(Annulus::new(tuning.shield_radius * 0.3, tuning.shield_radius * 0.4))),
+                MeshMaterial2d(materials.add(Color::srgba(0.8, 0.95, 1.0, 0.9))),
+                Transform::from_translation(contact.extend(2.0)),
+                ParryFlash { timer: Timer::from_seconds(0.3, TimerMode::Once) },
+                GameEntity,
+            ));
+            audio.play(sounds.parry.clone());
+            stats.players[player_id.index()].shots_parried += 1;
+            continue;
         }
+
+        commands.entity(proj_entity).despawn();
+        stats.players[proj.owner.index()].shots_hit += 1;
+        damage.write(DamageEvent {
+            target: player_entity,
+            source: Some(proj.owner),
+            amount: proj.damage,
+            kind: DamageKind::Projectile,
+        });
     }
 }
 
-
-fn projectile_shield_collision(
+/// Grows and fades the ring left by a parry
+fn fade_parry_flashes(
     mut commands: Commands,
-    projectile_query: Query<(Entity, &Transform, &Projectile)>,
-    mut player_query: Query<(Entity, &Transform, &Player, &mut ShieldHealth, &PlayerId, Option<&Children>, )>,
-    shielded_query: Query<&ChildOf, With<Shield>>,
+    time: Res<Time>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut query: Query<(Entity, &mut ParryFlash, &mut Transform, &MeshMaterial2d<ColorMaterial>)>,
 ) {
-    for (proj_entity, proj_tf, proj) in &projectile_query {
-        let proj_pos = proj_tf.translation.truncate();
-
-        for (player_entity, player_tf, player, mut shield, player_id, children) in &mut player_query {
-            if proj.owner == *player_id {
-                continue; // don't hit yourself
-            }
-
-            // Check if player has a shield child
-            let shield_active = children.map_or(false, |children| {
-                children.iter().any(|child| {
-                    // a shield child exists
-                    // safer than querying full shield list
-                    true // we already filtered in shield_system
-                })
-            });
-
-            let player_pos = player_tf.translation.truncate();
-            let distance = player_pos.distance(proj_pos);
-
-            if (distance < player.radius + proj.radius + 20.) && shield_active {
-                // Shield absorbs but does NOT destroy projectile
-                
-                if shield.shp - 100. < 0. {
-                    shield.shp = 0.;
-                } else {
-                    shield.shp -= 100.;
-                }                
-                if shield.shp <= 0. {
-                    if let Some(children) = children {
-                        for child in children.iter() {
-                            if shielded_query.get(child).is_ok() {
-                                commands.entity(child).despawn();
-                            }
-                        }
-                    }
-                }
-                commands.entity(proj_entity).despawn();
-                continue;
-            }
+    for (entity, mut flash, mut transform, material) in &mut query {
+        flash.timer.tick(time.delta());
+        if flash.timer.is_finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        transform.scale = Vec3::splat(1.0 + 2.0 * flash.timer.fraction());
+        if let Some(material) = materials.get_mut(&material.0) {
+            material.color.set_alpha(0.9 * flash.timer.fraction_remaining());
         }
     }
 }
 
-fn warp_drive(
+/// Flashes a shield that just took a hit
+fn flicker_shields(
+    mut commands: Commands,
     time: Res<Time>,
-    keyboard: Res<ButtonInput<KeyCode>>,
-    mut query: Query<(&PlayerId, &mut Transform, &mut WarpCooldown)>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut query: Query<(Entity, &mut ShieldFlicker, &MeshMaterial2d<ColorMaterial>)>,
 ) {
-    let warp_distance = 200.0;
-
-    for (id, mut transform, mut cooldown) in &mut query {
-        cooldown.timer.tick(time.delta());
-        if !cooldown.timer.finished() {
+    for (entity, mut flicker, material) in &mut query {
+        flicker.timer.tick(time.delta());
+        let Some(material) = materials.get_mut(&material.0) else {
             continue;
-        }
-        let warp_pressed = match id {
-            PlayerId::One => keyboard.just_pressed(KeyCode::KeyS),
-            PlayerId::Two => keyboard.just_pressed(KeyCode::ArrowDown),
         };
-
-        if warp_pressed {
-            let forward = (transform.rotation * Vec3::Y).truncate();
-            let new_pos = transform.translation + (forward.extend(0.0) * warp_distance);
-
-            let extents = Vec3::from((BOUNDS / 2.0, 0.0));
-            transform.translation = new_pos.min(extents).max(-extents);
-            cooldown.timer.reset();
+        if flicker.timer.is_finished() {
+            material.color.set_alpha(SHIELD_ALPHA);
+            commands.entity(entity).remove::<ShieldFlicker>();
+        } else {
+            let bright = ((flicker.timer.elapsed_secs() * 30.0) as u32).is_multiple_of(2);
+            material.color.set_alpha(if bright { 0.9 } else { SHIELD_ALPHA });
         }
     }
 }
 
-fn warp_drive_controller(
+fn warp_drive(
     time: Res<Time>,
-    controllers: Res<PlayerControllers>,
-    gamepads: Query<(Entity, &Gamepad)>,
-    mut query: Query<(&PlayerId, &Player, &mut Transform, &mut WarpCooldown)>,
+    mut query: Query<(&mut ShipInput, &mut Transform, &mut WarpCooldown)>,
+    arena: Res<Arena>,
 ) {
-    let warp_distance = 200.0;
-
-    for (id, ship, mut transform, mut cooldown) in &mut query {
+    for (mut input, mut transform, mut cooldown) in &mut query {
         cooldown.timer.tick(time.delta());
-        if !cooldown.timer.finished() {
-            continue;
-        }
-        let mut warp_pressed = false;
-        let Some(gamepad) = gamepad_for_player(&controllers, *id) else {
+        // A press during the cooldown is dropped rather than queued
+        let warp_pressed = std::mem::take(&mut input.warp);
+        if !cooldown.timer.is_finished() {
             continue;
-        };
-
-        // Match controller like the other controller systems
-        for (e, gp) in &gamepads {
-            if Some(e) == Some(gamepad) {
-                // SOUTH = warp button
-                if gp.just_pressed(GamepadButton::South) {
-                    warp_pressed = true;
-                    break;
-
-                }
-            }
         }
 
         if warp_pressed {
             let forward = (transform.rotation * Vec3::Y).truncate();
-            let new_pos = transform.translation + (forward.extend(0.0) * warp_distance);
+            let new_pos = transform.translation + (forward.extend(0.0) * cooldown.distance);
 
-            let extents = Vec3::from((BOUNDS / 2.0, 0.0));
-            transform.translation = new_pos.min(extents).max(-extents);
+            transform.translation = arena.confine(new_pos);
             cooldown.timer.reset();
         }
     }
 }
 
-
 fn projectile_movement(
+    mut commands: Commands,
     time: Res<Time>,
-    mut query: Query<(&mut Transform, &Projectile)>,
+    mut query: Query<(Entity, &mut Transform, &mut Projectile)>,
+    arena: Res<Arena>,
 ) {
-    for (mut transform, projectile) in &mut query {
+    for (entity, mut transform, mut projectile) in &mut query {
         let delta = projectile.velocity * time.delta_secs();
         transform.translation.x += delta.x;
         transform.translation.y += delta.y;
+
+        projectile.sweep = delta;
+        projectile.range -= delta.length();
+        projectile.lifetime.tick(time.delta());
+
+        // Fully past a wall it can't hit anything any more, past a seam it comes round
+        let outside = arena.is_outside(transform.translation.truncate(), projectile.radius);
+        if arena.wrap {
+            transform.translation = arena.confine(transform.translation);
+        }
+        if outside || projectile.range <= 0.0 || projectile.lifetime.is_finished() {
+            commands.entity(entity).despawn();
+        }
     }
 }
 
@@ -909,172 +1718,182 @@ fn projectile_color_for(
     }
 }
 
-fn fire_laser(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    query: Query<(&Transform, &Player, &PlayerId)>,
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
+fn reload_weapon(
+    time: Res<Time>,
     audio: Res<Audio>,
     sounds: Res<Sounds>,
+    mut query: Query<(&mut ShipInput, &mut Weapon)>,
 ) {
-    for (transform, _, id) in &query {
-
-        let shoot = match id {
-            PlayerId::One => keyboard_input.just_pressed(KeyCode::Space),
-            PlayerId::Two => keyboard_input.just_pressed(KeyCode::Enter),
-        };
-
-        if shoot {
-            let color = projectile_color_for(*id);
-
-            let forward = (transform.rotation * Vec3::Y).truncate().normalize();
-
-            let mesh = Circle::new(4.0).mesh().build();
-            let mesh_handle = meshes.add(mesh);
-
-            commands.spawn((
-                Mesh2d(mesh_handle),
-                MeshMaterial2d(materials.add(color)),
-                Transform::from_translation(transform.translation),
-                Projectile {
-                    velocity: forward * 400.0,
-                    radius: 5.0,
-                    owner: *id,
-                },
-            ));
-            audio.play(sounds.laser.clone());
+    for (mut input, mut weapon) in &mut query {
+        if std::mem::take(&mut input.reload) && weapon.start_reload() {
+            audio.play(sounds.reload.clone());
+        }
+        if !weapon.reloading {
+            continue;
+        }
+        weapon.reload.tick(time.delta());
+        if weapon.reload.is_finished() {
+            weapon.ammo = weapon.magazine;
+            weapon.reloading = false;
         }
     }
 }
 
-
-
-
-fn fire_laser_controller(
-    gamepads: Query<(Entity, &Gamepad)>,
-    controllers: Res<PlayerControllers>,
-    query: Query<(&Transform, &Player, &PlayerId)>,
+fn fire_laser(
+    // A picked up weapon takes over the trigger until it runs out
+    mut query: Query<(&Transform, &PlayerId, &Loadout, &mut Weapon, &mut ShipInput), Without<EquippedWeapon>>,
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     audio: Res<Audio>,
     sounds: Res<Sounds>,
+    mut stats: ResMut<MatchStats>,
+    tuning: Res<GameTuning>,
 ) {
-    for (transform, _player, id) in &query {
-        let mut shoot = false;
-
-        let Some(gamepad) = gamepad_for_player(&controllers, *id) else {
+    for (transform, id, loadout, mut weapon, mut input) in &mut query {
+        if !std::mem::take(&mut input.fire) {
+            continue;
+        }
+        if weapon.reloading || weapon.ammo == 0 {
+            audio.play(sounds.empty.clone());
             continue;
-        };        // Loop over every connected controller (same pattern as rotation + thrust controller)
-        for (e, gp) in &gamepads {
-            // Compare controller to assigned controller
-            if Some(e) == Some(gamepad) {
-                // West button = Fire
-                if gp.just_pressed(GamepadButton::West) {
-                    shoot = true;
-                    break;
-                }
-            }
         }
 
-        if shoot {
-            let color = projectile_color_for(*id);
+        let color = projectile_color_for(*id);
 
-            let forward = (transform.rotation * Vec3::Y).truncate().normalize();
+        let forward = (transform.rotation * Vec3::Y).truncate().normalize();
+        let side = forward.perp();
 
-            let mesh = Circle::new(4.0).mesh().build();
-            let mesh_handle = meshes.add(mesh);
+        // Drawn a touch smaller than it hits
+        let mesh = Circle::new(loadout.projectile_radius - 1.0).mesh().build();
+        let mesh_handle = meshes.add(mesh);
+        let material = materials.add(color);
 
+        // Barrels sit side by side, centred on the nose
+        for barrel in 0..loadout.barrels {
+            let offset = (barrel as f32 - (loadout.barrels - 1) as f32 / 2.0) * loadout.barrel_spacing;
             commands.spawn((
-                Mesh2d(mesh_handle),
-                MeshMaterial2d(materials.add(color)),
-                Transform::from_translation(transform.translation),
+                Mesh2d(mesh_handle.clone()),
+                MeshMaterial2d(material.clone()),
+                Transform::from_translation(transform.translation + (side * offset).extend(0.0)),
                 Projectile {
-                    velocity: forward * 400.0,
-                    radius: 5.0,
+                    velocity: forward * loadout.projectile_speed,
+                    radius: loadout.projectile_radius,
                     owner: *id,
+                    damage: loadout.projectile_damage,
+                    lifetime: Timer::from_seconds(tuning.projectile_lifetime, TimerMode::Once),
+                    range: loadout.projectile_range,
+                    sweep: Vec2::ZERO,
+                    half_length: 0.0,
                 },
+                Wraps { radius: loadout.projectile_radius },
+                GameEntity,
             ));
+        }
+        audio.play(sounds.laser.clone());
+        stats.players[id.index()].shots_fired += loadout.barrels;
 
-            audio.play(sounds.laser.clone());
+        weapon.ammo -= 1;
+        if weapon.ammo == 0 && weapon.auto_reload && weapon.start_reload() {
+            audio.play(sounds.reload.clone());
         }
     }
 }
 
-
 fn projectile_player_collision(
     mut commands: Commands,
     projectile_query: Query<(Entity, &Transform, &Projectile)>,
-    mut player_query: Query<(Entity, &Transform, &Player, &PlayerId, &mut Health)>,
-    audio: Res<Audio>,
-    sounds: Res<Sounds>,
+    player_query: Query<(Entity, &Transform, &Player, &PlayerId)>,
+    mut damage: MessageWriter<DamageEvent>,
+    mut stats: ResMut<MatchStats>,
+    grid: Res<BroadPhase>,
+    time: Res<Time>,
+    arena: Res<Arena>,
 ) {
     for (proj_entity, proj_tf, proj) in &projectile_query {
         let proj_pos = proj_tf.translation.truncate();
 
-        for (player_entity, player_tf, player, player_id, mut health) in &mut player_query {
-            if proj.owner == *player_id {
-                continue; // don't hit yourself
-            }
-
-            let player_pos = player_tf.translation.truncate();
-            let distance = player_pos.distance(proj_pos);
-
-            if distance < player.radius + proj.radius {
-                // hit detected
-                commands.entity(proj_entity).despawn();
+        // Whichever ship the shot reached first along its path
+        let hit = grid
+            .near(proj_pos, proj.extent() + proj.radius)
+            .into_iter()
+            .filter_map(|candidate| player_query.get(candidate).ok())
+            .filter(|(_, _, _, player_id)| proj.owner != **player_id) // don't hit yourself
+            .filter_map(|(player_entity, player_tf, player, _)| {
+                let player_pos = player_tf.translation.truncate();
+                let step = player.velocity * time.delta_secs();
+                proj.hit_time(&arena, proj_pos, player_pos, step, player.radius + proj.radius)
+                    .map(|t| (t, player_entity))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+        let Some((_, player_entity)) = hit else {
+            continue;
+        };
 
-                if (health.hp as f32 / 100.) - (health.hp as f32 /100.) != (health.hp % 100) as f32 {
-                    let last_num = health.hp % 100;
-                    health.hp -= last_num;
-                } else {
-                    health.hp -= 100;
-                }
+        // hit detected
+        commands.entity(proj_entity).despawn();
+        stats.players[proj.owner.index()].shots_hit += 1;
+        damage.write(DamageEvent {
+            target: player_entity,
+            source: Some(proj.owner),
+            amount: proj.damage,
+            kind: DamageKind::Projectile,
+        });
+    }
+}
 
-                if health.hp <= 0 {
-                    commands.entity(player_entity).despawn();
-                }
-                break;
-            }
-        }
+/// Elastic bounce between two bodies, `normal` pointing from the first to the
+/// second. Gives back how fast they hit, or nothing if already moving apart.
+fn bounce(normal: Vec2, v1: &mut Vec2, m1: f32, v2: &mut Vec2, m2: f32) -> Option<f32> {
+    let closing = (*v1 - *v2).dot(normal);
+    if closing <= 0.0 {
+        return None;
     }
+    let impulse = 2.0 * closing / (m1 + m2);
+    *v1 -= normal * impulse * m2;
+    *v2 += normal * impulse * m1;
+    Some(closing)
+}
+
+/// Hull lost by a ship in a collision closing at `closing` speed
+fn impact_damage(closing: f32, tuning: &GameTuning) -> i32 {
+    ((closing - tuning.impact_safe_speed).max(0.0) * tuning.impact_damage) as i32
 }
 
 fn player_player_collision(
-    mut commands: Commands,
-    query: Query<(Entity, &Transform, &Player, &PlayerId)>,
-    mut hq: Query<&mut Health>,
+    mut query: Query<(Entity, &mut Transform, &mut Player, &PlayerId)>,
+    mut damage: MessageWriter<DamageEvent>,
+    tuning: Res<GameTuning>,
+    arena: Res<Arena>,
 ) {
-    let players: Vec<_> = query.iter().collect();
+    let mut pairs = query.iter_combinations_mut();
+    while let Some([(e1, mut tf1, mut p1, id1), (e2, mut tf2, mut p2, id2)]) = pairs.fetch_next() {
+        let delta = arena.delta(tf1.translation.truncate(), tf2.translation.truncate());
+        let dist = delta.length();
+        let min_dist = p1.radius + p2.radius;
+        if dist >= min_dist {
+            continue;
+        }
 
-    if players.len() < 2 { return; }
+        // Mass goes with hull size, same as asteroids
+        let m1 = p1.radius * p1.radius;
+        let m2 = p2.radius * p2.radius;
+        let normal = delta.normalize_or(Vec2::X);
 
-    let (e1, tf1, p1, id1) = players[0];
-    let (e2, tf2, p2, id2) = players[1];
+        // Push apart every frame they overlap so they never stick
+        let overlap = min_dist - dist;
+        tf1.translation -= (normal * overlap * m2 / (m1 + m2)).extend(0.0);
+        tf2.translation += (normal * overlap * m1 / (m1 + m2)).extend(0.0);
 
-    let pos1 = tf1.translation.truncate();
-    let pos2 = tf2.translation.truncate();
+        let Some(closing) = bounce(normal, &mut p1.velocity, m1, &mut p2.velocity, m2) else {
+            continue;
+        };
 
-    let dist = pos1.distance(pos2);
-    if dist < p1.radius + p2.radius {
-        let v1 = p1.velocity.length();
-        let v2 = p2.velocity.length();
-        if v1 > v2 {
-            if let Ok(mut h2) = hq.get_mut(e2) {
-                h2.hp -= 5;
-                if h2.hp <= 0 {
-                    commands.entity(e2).despawn();
-                }
-            }
-        } else {
-            if let Ok(mut h1) = hq.get_mut(e1) {
-                h1.hp -= 5;
-                if h1.hp <= 0 {
-                    commands.entity(e1).despawn();
-                }
-            }
-        }
+        let amount = impact_damage(closing, &tuning);
+        damage.write_batch([
+            DamageEvent { target: e1, source: Some(*id2), amount, kind: DamageKind::Impact },
+            DamageEvent { target: e2, source: Some(*id1), amount, kind: DamageKind::Impact },
+        ]);
     }
 }
 
@@ -1082,19 +1901,20 @@ fn spawn_asteroid(
     time: Res<Time>,
     mut spawner: ResMut<AsteroidSpawner>,
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut factory: AsteroidFactory,
+    tuning: Res<GameTuning>,
 ) {
+    spawner.timer.set_duration(Duration::from_secs_f32(tuning.asteroid_interval));
     spawner.timer.tick(time.delta());
-    if !spawner.timer.finished() {
+    if !spawner.timer.is_finished() {
         return;
     }
 
     // ----- Generate random spawn position along screen edges -----
-    let mut rng = rand::thread_rng();
+    let mut rng = rand::rng();
 
     // Randomly pick which edge to spawn on
-    let side = rng.gen_range(0..4);
+    let side = rng.random_range(0..4);
     let x;
     let y;
 
@@ -1103,20 +1923,20 @@ fn spawn_asteroid(
 
     match side {
         0 => { // Top
-            x = rng.gen_range(-half.x..half.x);
+            x = rng.random_range(-half.x..half.x);
             y = half.y;
         }
         1 => { // Bottom
-            x = rng.gen_range(-half.x..half.x);
+            x = rng.random_range(-half.x..half.x);
             y = -half.y;
         }
         2 => { // Left
             x = -half.x;
-            y = rng.gen_range(-half.y..half.y);
+            y = rng.random_range(-half.y..half.y);
         }
         _ => { // Right
             x = half.x;
-            y = rng.gen_range(-half.y..half.y);
+            y = rng.random_range(-half.y..half.y);
         }
     }
 
@@ -1124,92 +1944,137 @@ fn spawn_asteroid(
     
     let center = Vec2::ZERO;
     let direction_to_center = (center - Vec2::new(x, y)).normalize();
-    let mut direction = direction_to_center;
-
-    /*
-    let angle_offset = rng.gen_range(-0.5..0.5); // ± ~30 degrees
-    let offset = Vec2::new(angle_offset,angle_offset);
-    direction = direction.rotate(offset);
-    */
 
-    
+    // Aim somewhere around the middle rather than dead centre
+    let spread = tuning.asteroid_approach_spread;
+    let angle_offset = if spread > 0.0 { rng.random_range(-spread..spread) } else { 0.0 };
+    let direction = Vec2::from_angle(angle_offset).rotate(direction_to_center);
 
     // ----- Give asteroid a random velocity toward center-ish -----
-    let speed = rng.gen_range(20.0..70.0);
+    let speed = rng.random_range(tuning.asteroid_speed.0..=tuning.asteroid_speed.1);
 
     let velocity = direction * speed;
 
-    // ----- Create asteroid mesh -----
-    let mesh = meshes.add(Circle::new(70.0).mesh());
-    let material = materials.add(Color::srgb(0.8, 0.8, 0.8));
-
     // ----- Spawn asteroid entity -----
-    commands.spawn((
-        Mesh2d(mesh.into()),
-        MeshMaterial2d(material),
-        Transform::from_translation(spawn_pos),
-        Asteroid { 
-            velocity,
-            radius: 70.0,
-        },
-        GameEntity,
-    ));
+    factory.spawn(&mut commands, AsteroidSize::random(&mut rng), spawn_pos, velocity);
 }
 
 fn move_asteroids(
+    mut commands: Commands,
     time: Res<Time>,
-    mut query: Query<(&mut Transform, &Asteroid)>
+    mut query: Query<(Entity, &mut Transform, &Asteroid)>,
+    arena: Res<Arena>,
 ) {
-    for (mut transform, asteroid) in &mut query {
+    for (entity, mut transform, asteroid) in &mut query {
         let delta = asteroid.velocity * time.delta_secs();
         transform.translation.x += delta.x;
         transform.translation.y += delta.y;
+        transform.rotate_z(asteroid.spin * time.delta_secs());
+        if arena.wrap {
+            transform.translation = arena.confine(transform.translation);
+            continue;
+        }
+
+        // Gone once it has drifted fully off screen and is still heading away
+        let pos = transform.translation.truncate();
+        if arena.is_outside(pos, asteroid.radius) && pos.dot(asteroid.velocity) > 0.0 {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Bounces asteroids off each other, heavier (bigger) rocks giving way less
+fn asteroid_asteroid_collision(
+    mut query: Query<(Entity, &mut Transform, &mut Asteroid)>,
+    grid: Res<BroadPhase>,
+    arena: Res<Arena>,
+) {
+    let asteroids: Vec<(Entity, Vec2, f32)> = query
+        .iter()
+        .map(|(entity, tf, asteroid)| (entity, tf.translation.truncate(), asteroid.radius))
+        .collect();
+    for (e1, pos, radius) in asteroids {
+        // Each pair once, from its lower entity
+        for e2 in grid.near(pos, radius).into_iter().filter(|e2| *e2 > e1) {
+            let Ok([(_, mut tf1, mut a1), (_, mut tf2, mut a2)]) = query.get_many_mut([e1, e2]) else {
+                continue;
+            };
+            let delta = arena.delta(tf1.translation.truncate(), tf2.translation.truncate());
+            let dist = delta.length();
+            let min_dist = a1.radius + a2.radius;
+            if dist >= min_dist || dist == 0.0 {
+                continue;
+            }
+
+            let normal = delta / dist;
+            // Mass goes with area
+            let m1 = a1.radius * a1.radius;
+            let m2 = a2.radius * a2.radius;
+            // Already moving apart, e.g. fragments that have just split
+            if bounce(normal, &mut a1.velocity, m1, &mut a2.velocity, m2).is_none() {
+                continue;
+            }
+
+            // Push them out of each other so they don't stick
+            let overlap = min_dist - dist;
+            tf1.translation -= (normal * overlap * m2 / (m1 + m2)).extend(0.0);
+            tf2.translation += (normal * overlap * m1 / (m1 + m2)).extend(0.0);
+        }
     }
 }
 
 
 fn setup_asteroid_spawning (
         mut commands: Commands,
+        tuning: Res<GameTuning>,
     ) {
     commands.insert_resource(AsteroidSpawner {
-        timer: Timer::from_seconds(5.0,TimerMode::Repeating),
+        timer: Timer::from_seconds(tuning.asteroid_interval,TimerMode::Repeating),
     });
 }
 
 fn ship_asteroid_collision(
-    mut commands: Commands,
-    mut players: Query<(Entity, &Transform, &Player, &mut Health)>,
-    asteroids: Query<(Entity, &Transform, &Asteroid), Without<Player>>,
+    mut players: Query<(Entity, &mut Transform, &mut Player)>,
+    mut asteroids: Query<(&mut Transform, &mut Asteroid, &AsteroidShape), Without<Player>>,
+    mut damage: MessageWriter<DamageEvent>,
+    grid: Res<BroadPhase>,
+    tuning: Res<GameTuning>,
+    arena: Res<Arena>,
 ) {
-    for (player_entity, player_transform, player, mut health) in players.iter_mut() {
-        let player_pos = player_transform.translation.truncate();
-        let player_radius = player.radius;
+    for (player_entity, mut player_transform, mut player) in players.iter_mut() {
+        let ship_mass = player.radius * player.radius;
 
-        for (asteroid_entity, asteroid_transform, asteroid) in asteroids.iter() {
-            let asteroid_pos = asteroid_transform.translation.truncate();
-            let asteroid_radius = asteroid.radius;
-
-            let distance = player_pos.distance(asteroid_pos);
-
-            if distance < player_radius + asteroid_radius {
-                // Damage player
+        for asteroid_entity in grid.near(player_transform.translation.truncate(), player.radius) {
+            let Ok((mut asteroid_transform, mut asteroid, shape)) = asteroids.get_mut(asteroid_entity) else {
+                continue;
+            };
+            let offset = arena.delta(
+                asteroid_transform.translation.truncate(),
+                player_transform.translation.truncate(),
+            );
+            let Some((normal, depth)) =
+                shape.contact(asteroid_transform.rotation, offset, player.radius)
+            else {
+                continue;
+            };
 
-                let even = health.hp % 100;
-                if even == 0 {
-                    health.hp -= 100;
-                } else {
-                    health.hp = health.hp - even;
-                }
-                if health.hp<= 0 {
-                    commands.entity(player_entity).despawn();
-                }
+            let rock_mass = asteroid.radius * asteroid.radius;
+            let total = ship_mass + rock_mass;
+            asteroid_transform.translation -= (normal * depth * ship_mass / total).extend(0.0);
+            player_transform.translation += (normal * depth * rock_mass / total).extend(0.0);
 
-                // Destroy asteroid
-                commands.entity(asteroid_entity).despawn();
+            let Some(closing) =
+                bounce(normal, &mut asteroid.velocity, rock_mass, &mut player.velocity, ship_mass)
+            else {
+                continue;
+            };
 
-                // Optional: break so one asteroid only hits once
-                break;
-            }
+            damage.write(DamageEvent {
+                target: player_entity,
+                source: None,
+                amount: impact_damage(closing, &tuning),
+                kind: DamageKind::Impact,
+            });
         }
     }
 }
@@ -1218,100 +2083,120 @@ fn ship_asteroid_collision(
 fn projectile_asteroid_collision(
     mut commands: Commands,
     projectile_query: Query<(Entity, &Transform, &Projectile), Without<Asteroid>>,
-    asteroid_query: Query<(Entity, &Transform, &Asteroid), Without<Projectile>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>
+    asteroid_query: Query<(Entity, &Transform, &Asteroid, &AsteroidShape), Without<Projectile>>,
+    mut factory: AsteroidFactory,
+    mut destroyed: MessageWriter<AsteroidDestroyed>,
+    mut stats: ResMut<MatchStats>,
+    grid: Res<BroadPhase>,
+    time: Res<Time>,
+    tuning: Res<GameTuning>,
+    arena: Res<Arena>,
 ) {
+    // Rocks already broken this tick, so two shots can't split the same one twice
+    let mut broken = Vec::new();
+
     // Check each projectile
     for (projectile_entity, projectile_transform, projectile) in projectile_query.iter() {
         let projectile_pos = projectile_transform.translation.truncate();
 
-        // Check each asteroid
-        for (asteroid_entity, asteroid_transform, asteroid) in asteroid_query.iter() {
-            let asteroid_pos = asteroid_transform.translation.truncate();
-
-            let distance = projectile_pos.distance(asteroid_pos);
-
-            if distance < projectile.radius + asteroid.radius {
-                // Hit!
-                commands.entity(asteroid_entity).despawn();
-                commands.entity(projectile_entity).despawn();
-
-                if asteroid.radius > 30.0 {
-                    let new_radius = asteroid.radius * 0.5;
-
-                    let mut rng = rand::thread_rng();
-
-                    let num_children = rng.gen_range(2..4);
-
-                    for _ in 0..num_children {
-                        let angle = rng.gen_range(0.0..std::f32::consts::TAU);
-                        let direction = Vec2::from_angle(angle);
-
-                        // child velocity
-                        let speed = rng.gen_range(60.0..160.0);
-                        let velocity = direction * speed;
-
-                        // Build mesh for child
-                        let mesh = meshes.add(Circle::new(new_radius).mesh());
-                        let material = materials.add(Color::srgb(0.8, 0.8, 0.8));
-                        commands.spawn((
-                                Mesh2d(mesh),
-                                MeshMaterial2d(material),
-                                Transform::from_translation(
-                                    asteroid_transform.translation,
-                                ),
-                            Asteroid {
-                                radius: new_radius,
-                                velocity,
-                            },
-                            GameEntity,
-                        ));
-                    }
-                } else {
-                        let angle = rng().gen_range(0.0..std::f32::consts::TAU);
-
-                        // child velocity
-                        let speed = rng().gen_range(10.0..60.0);
-                        let mesh = meshes.add(Circle::new(7.0).mesh());
-                        let material = materials.add(Color::srgb(0.2, 0.8, 0.8));
-                        commands.spawn((
-                                Mesh2d(mesh),
-                                MeshMaterial2d(material),
-                                Transform::from_translation(
-                                    asteroid_transform.translation,
-                                ),
-                            Nickel {
-                                radius: 7.0,
-                            },
-                            GameEntity,
-                        ));
-                }
-                break; // stop checking after hit
+        // Whichever asteroid the shot reached first along its path
+        let reach = projectile.extent() + projectile.radius;
+        let hit = grid
+            .near(projectile_pos, reach)
+            .into_iter()
+            .filter(|asteroid_entity| !broken.contains(asteroid_entity))
+            .filter_map(|asteroid_entity| asteroid_query.get(asteroid_entity).ok())
+            .filter_map(|(asteroid_entity, asteroid_transform, asteroid, shape)| {
+                // In the asteroid's frame, from where the shot set off this tick
+                let offset = arena.delta(asteroid_transform.translation.truncate(), projectile_pos);
+                let (start, sweep) = projectile.relative_path(offset, asteroid.velocity * time.delta_secs());
+                shape
+                    .sweep_circle(asteroid_transform.rotation, start, sweep, projectile.radius)
+                    .map(|t| (t, asteroid_entity, asteroid_transform, asteroid))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+
+        let Some((_, asteroid_entity, asteroid_transform, asteroid)) = hit else {
+            continue;
+        };
+
+        broken.push(asteroid_entity);
+        commands.entity(asteroid_entity).despawn();
+        commands.entity(projectile_entity).despawn();
+        stats.players[projectile.owner.index()].asteroids_destroyed += 1;
+        destroyed.write(AsteroidDestroyed {
+            position: asteroid_transform.translation,
+            radius: asteroid.radius,
+        });
+
+        if let Some(smaller) = asteroid.size.smaller() {
+            let mut rng = rand::rng();
+
+            let num_children = rng.random_range(2..4);
+
+            for _ in 0..num_children {
+                let angle = rng.random_range(0.0..std::f32::consts::TAU);
+                let direction = Vec2::from_angle(angle);
+
+                // child velocity
+                let speed = rng.random_range(tuning.fragment_speed.0..=tuning.fragment_speed.1);
+                let velocity = direction * speed;
+
+                factory.spawn(&mut commands, smaller, asteroid_transform.translation, velocity);
             }
+        } else if rng().random_bool(tuning.weapon_drop_chance as f64) {
+            weapons::spawn_pickup(
+                &mut commands,
+                &mut factory.meshes,
+                &mut factory.materials,
+                asteroid_transform.translation,
+            );
+        } else {
+                let mesh = factory.meshes.add(Circle::new(tuning.nickel_radius).mesh());
+                let material = factory.materials.add(Color::srgb(0.2, 0.8, 0.8));
+                commands.spawn((
+                        Mesh2d(mesh),
+                        MeshMaterial2d(material),
+                        Transform::from_translation(
+                            asteroid_transform.translation,
+                        ),
+                    Nickel {
+                        radius: tuning.nickel_radius,
+                    },
+                    GameEntity,
+                ));
         }
     }
 }
 
 fn ship_nickel_collision(
     mut commands: Commands,
-    mut players: Query<(Entity, &Transform, &mut ShieldHealth, &Player)>,
+    mut players: Query<(&Transform, &mut ShieldHealth, &Player, &PlayerId)>,
     asteroids: Query<(Entity, &Transform, &Nickel), Without<Player>>,
+    mut stats: ResMut<MatchStats>,
+    grid: Res<BroadPhase>,
+    tuning: Res<GameTuning>,
+    arena: Res<Arena>,
 ) {
 
-    for (player_entity, player_transform, mut shp, player ) in players.iter_mut() {
+    for (player_transform, mut shp, player, id) in players.iter_mut() {
         let player_pos = player_transform.translation.truncate();
         let player_radius = player.radius;
 
-        for (nickel_entity, nickel_transform, nickel) in asteroids.iter() {
+        for candidate in grid.near(player_pos, player_radius) {
+            let Ok((nickel_entity, nickel_transform, nickel)) = asteroids.get(candidate) else {
+                continue;
+            };
             let nickel_pos = nickel_transform.translation.truncate();
             let nickel_radius = nickel.radius;
 
-            let distance = player_pos.distance(nickel_pos);
+            let distance = arena.distance(player_pos, nickel_pos);
 
             if distance < player_radius + nickel_radius {
-                // Damage player
-                shp.shp += 100.0;
+                if shp.shp < shp.max {
+                    shp.restore(tuning.nickel_shield);
+                }
+                stats.players[id.index()].nickels_collected += 1;
 
                 // Destroy asteroid
                 commands.entity(nickel_entity).despawn();
@@ -0,0 +1,276 @@
+use bevy::prelude::*;
+
+use crate::{
+    arena::Arena, asteroids::AsteroidShape, weapons::WeaponPickup, GameState, Nickel, Player,
+    BOUNDS,
+};
+
+/// Widest a broad phase cell may be, a bit under twice the biggest asteroid
+const CELL_SIZE: f32 = 128.0;
+
+pub struct PhysicsPlugin;
+
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BroadPhase>()
+            .configure_sets(
+                FixedUpdate,
+                (
+                    PhysicsSet::Input,
+                    PhysicsSet::Integrate,
+                    PhysicsSet::BroadPhase,
+                    PhysicsSet::Collide,
+                    PhysicsSet::Damage,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(FixedUpdate, rebuild_broad_phase.in_set(PhysicsSet::BroadPhase))
+            .add_systems(
+                RunFixedMainLoop,
+                (
+                    restore_simulated_transforms.in_set(RunFixedMainLoopSystems::BeforeFixedMainLoop),
+                    interpolate_transforms.in_set(RunFixedMainLoopSystems::AfterFixedMainLoop),
+                ),
+            )
+            .add_systems(FixedLast, record_simulated_transforms);
+    }
+}
+
+/// Steps of one physics tick, run in this order every `FixedUpdate`
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PhysicsSet {
+    /// Ships act on what their pilots asked for: turning, thrust, guns, warp, shields
+    Input,
+    /// Everything moves on by its velocity
+    Integrate,
+    /// The `BroadPhase` grid is rebuilt from where things ended up
+    BroadPhase,
+    /// Overlaps are found and pushed apart
+    Collide,
+    /// Hits settle into hull and shield
+    Damage,
+}
+
+/// Drawn part way between its last two physics ticks so motion stays smooth
+/// whatever the frame rate. Outside `FixedUpdate` its `Transform` is the blend,
+/// inside it is the simulated position.
+#[derive(Component, Default)]
+pub struct Interpolated {
+    previous: Transform,
+    current: Transform,
+    /// Nothing to blend until a tick has been recorded since spawning
+    ticked: bool,
+}
+
+fn restore_simulated_transforms(mut query: Query<(&mut Transform, &Interpolated)>) {
+    for (mut transform, interpolated) in &mut query {
+        if interpolated.ticked {
+            transform.translation = interpolated.current.translation;
+            transform.rotation = interpolated.current.rotation;
+        }
+    }
+}
+
+fn record_simulated_transforms(mut query: Query<(&Transform, &mut Interpolated)>) {
+    for (transform, mut interpolated) in &mut query {
+        interpolated.previous = if interpolated.ticked { interpolated.current } else { *transform };
+        interpolated.current = *transform;
+        interpolated.ticked = true;
+    }
+}
+
+fn interpolate_transforms(
+    fixed_time: Res<Time<Fixed>>,
+    arena: Res<Arena>,
+    mut query: Query<(&mut Transform, &Interpolated)>,
+) {
+    let t = fixed_time.overstep_fraction();
+    for (mut transform, interpolated) in &mut query {
+        if !interpolated.ticked {
+            continue;
+        }
+        let previous = interpolated.previous.translation;
+        // The short way round, so crossing a seam doesn't sweep across the screen
+        let step = arena.delta(previous.truncate(), interpolated.current.translation.truncate());
+        let blended = previous + (step * t).extend(0.0);
+        transform.translation = if arena.wrap { arena.confine(blended) } else { blended };
+        transform.rotation = interpolated.previous.rotation.slerp(interpolated.current.rotation, t);
+    }
+}
+
+/// How far along `sweep`, as a fraction, a point first comes within `reach` of
+/// something `offset` away from where it started. Nothing if it never does.
+pub fn sweep_entry(offset: Vec2, sweep: Vec2, reach: f32) -> Option<f32> {
+    let clearance = offset.length_squared() - reach * reach;
+    if clearance < 0.0 {
+        return Some(0.0);
+    }
+    let a = sweep.length_squared();
+    if a == 0.0 {
+        return None;
+    }
+    // |sweep * t - offset| = reach, taking the earlier root
+    let b = -2.0 * offset.dot(sweep);
+    let discriminant = b * b - 4.0 * a * clearance;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let t = (-b - discriminant.sqrt()) / (2.0 * a);
+    (0.0..=1.0).contains(&t).then_some(t)
+}
+
+/// Uniform grid over the arena, rebuilt every tick, so collision checks only
+/// look at what is nearby instead of at everything. Holds ships, asteroids,
+/// nickels and weapon pickups; projectiles do the looking.
+#[derive(Resource)]
+pub struct BroadPhase {
+    columns: i32,
+    rows: i32,
+    /// Sized so the cells tile the arena exactly, and the seam falls on a cell edge
+    cell: Vec2,
+    wrap: bool,
+    cells: Vec<Vec<Entity>>,
+    /// Hand back every body from `near`, to compare against checking everything
+    pub brute_force: bool,
+}
+
+impl Default for BroadPhase {
+    fn default() -> Self {
+        let columns = (BOUNDS.x / CELL_SIZE).ceil() as i32;
+        let rows = (BOUNDS.y / CELL_SIZE).ceil() as i32;
+        Self {
+            columns,
+            rows,
+            cell: BOUNDS / Vec2::new(columns as f32, rows as f32),
+            wrap: false,
+            cells: vec![Vec::new(); (columns * rows) as usize],
+            brute_force: false,
+        }
+    }
+}
+
+impl BroadPhase {
+    /// Every body whose bounds come within `reach` of `pos`, each once
+    pub fn near(&self, pos: Vec2, reach: f32) -> Vec<Entity> {
+        let mut found: Vec<Entity> = if self.brute_force {
+            self.cells.iter().flatten().copied().collect()
+        } else {
+            self.cells_around(pos, reach)
+                .flat_map(|cell| self.cells[cell].iter().copied())
+                .collect()
+        };
+        // Bodies spanning several cells turn up once per cell
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+
+    fn insert(&mut self, entity: Entity, pos: Vec2, radius: f32) {
+        for cell in self.cells_around(pos, radius).collect::<Vec<_>>() {
+            self.cells[cell].push(entity);
+        }
+    }
+
+    fn cells_around(&self, pos: Vec2, reach: f32) -> impl Iterator<Item = usize> + '_ {
+        let corner = BOUNDS / 2.0;
+        let low = ((pos - reach + corner) / self.cell).floor().as_ivec2();
+        let high = ((pos + reach + corner) / self.cell).floor().as_ivec2();
+        let columns = self.span(low.x, high.x, self.columns);
+        let rows = self.span(low.y, high.y, self.rows);
+        rows.flat_map(move |row| columns.clone().map(move |column| (row * self.columns + column) as usize))
+    }
+
+    /// Cell indices from `low` to `high` along one axis, round the seam when
+    /// wrapping and held at the edge when walled
+    fn span(&self, low: i32, high: i32, count: i32) -> impl Iterator<Item = i32> + Clone {
+        let (low, high) = if self.wrap {
+            if high - low + 1 >= count { (0, count - 1) } else { (low, high) }
+        } else {
+            (low.clamp(0, count - 1), high.clamp(0, count - 1))
+        };
+        let wrap = self.wrap;
+        (low..=high).map(move |i| if wrap { i.rem_euclid(count) } else { i })
+    }
+}
+
+fn rebuild_broad_phase(
+    mut grid: ResMut<BroadPhase>,
+    arena: Res<Arena>,
+    bodies: Query<
+        (Entity, &Transform, Option<&Interpolated>),
+        Or<(With<Player>, With<AsteroidShape>, With<Nickel>, With<WeaponPickup>)>,
+    >,
+    ships: Query<&Player>,
+    asteroids: Query<&AsteroidShape>,
+    nickels: Query<&Nickel>,
+    pickups: Query<&WeaponPickup>,
+) {
+    grid.wrap = arena.wrap;
+    for cell in &mut grid.cells {
+        cell.clear();
+    }
+
+    for (entity, transform, interpolated) in &bodies {
+        let radius = if let Ok(ship) = ships.get(entity) {
+            ship.radius
+        } else if let Ok(shape) = asteroids.get(entity) {
+            shape.max_radius()
+        } else if let Ok(nickel) = nickels.get(entity) {
+            nickel.radius
+        } else if let Ok(pickup) = pickups.get(entity) {
+            pickup.radius
+        } else {
+            continue;
+        };
+        let pos = transform.translation.truncate();
+        // Grown by this tick's move, so swept checks still find things that
+        // were somewhere else when the tick began
+        let step = interpolated
+            .filter(|interpolated| interpolated.ticked)
+            .map_or(0.0, |interpolated| {
+                arena.distance(interpolated.current.translation.truncate(), pos)
+            });
+        grid.insert(entity, pos, radius + step);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wrapped_grid() -> BroadPhase {
+        BroadPhase { wrap: true, ..default() }
+    }
+
+    #[test]
+    fn cells_tile_the_arena() {
+        let grid = BroadPhase::default();
+        assert_eq!(grid.cell * Vec2::new(grid.columns as f32, grid.rows as f32), BOUNDS);
+        assert!(grid.cell.cmple(Vec2::splat(CELL_SIZE)).all());
+    }
+
+    #[test]
+    fn pairs_across_the_side_seam() {
+        let mut grid = wrapped_grid();
+        let asteroid = Entity::from_raw_u32(1).unwrap();
+        grid.insert(asteroid, Vec2::new(-590.0, 0.0), 77.0);
+        assert!(grid.near(Vec2::new(530.0, 0.0), 17.0).contains(&asteroid));
+    }
+
+    #[test]
+    fn pairs_across_the_top_seam() {
+        let mut grid = wrapped_grid();
+        let asteroid = Entity::from_raw_u32(1).unwrap();
+        grid.insert(asteroid, Vec2::new(0.0, 310.0), 40.0);
+        assert!(grid.near(Vec2::new(0.0, -300.0), 17.0).contains(&asteroid));
+    }
+
+    #[test]
+    fn walls_keep_the_edges_apart() {
+        let mut grid = BroadPhase::default();
+        let asteroid = Entity::from_raw_u32(1).unwrap();
+        grid.insert(asteroid, Vec2::new(-590.0, 0.0), 77.0);
+        assert!(!grid.near(Vec2::new(530.0, 0.0), 17.0).contains(&asteroid));
+    }
+}
@@ -0,0 +1,355 @@
+use bevy::{
+    asset::{io::Reader, AssetLoadFailedEvent, AssetLoader, LoadContext, LoadedFolder},
+    prelude::*,
+};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{controllers::PlayerActions, GameState, MatchScore, PlayerId};
+
+const PLAYERS: [PlayerId; 2] = [PlayerId::One, PlayerId::Two];
+
+pub struct ShipsPlugin;
+
+impl Plugin for ShipsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<ShipDef>()
+            .init_asset_loader::<ShipDefLoader>()
+            .init_resource::<ShipChoices>()
+            .add_systems(Startup, load_ship_roster)
+            .add_systems(OnEnter(GameState::ShipSelect), spawn_ship_select)
+            .add_systems(
+                Update,
+                (
+                    fill_ship_roster,
+                    (ship_select_input, update_ship_select_text)
+                        .chain()
+                        .run_if(in_state(GameState::ShipSelect)),
+                ),
+            );
+    }
+}
+
+/// A ship archetype, read from a `.ship.ron` file in `assets/ships`
+#[derive(Asset, TypePath, Debug)]
+pub struct ShipDef {
+    pub name: String,
+    pub movement_speed: f32,
+    /// Radians per second
+    pub rotation_speed: f32,
+    pub radius: f32,
+    pub max_health: i32,
+    /// Taken off every hit that gets past the shield
+    pub armor: i32,
+    pub max_shield: f32,
+    pub shield: ShieldStats,
+    pub warp_distance: f32,
+    /// Seconds between warps
+    pub warp_cooldown: f32,
+    pub weapon: Loadout,
+    /// Red then blue
+    pub sprites: [Handle<Image>; 2],
+}
+
+impl ShipDef {
+    /// The original interceptor, so there is still a ship to fly when
+    /// `assets/ships` has nothing usable in it
+    fn fallback(asset_server: &AssetServer) -> Self {
+        Self {
+            name: "Interceptor".to_string(),
+            movement_speed: 500.0,
+            rotation_speed: 300f32.to_radians(),
+            radius: 17.0,
+            max_health: 500,
+            armor: 0,
+            max_shield: 500.0,
+            shield: ShieldStats {
+                drain: 60.0,
+                regen: 45.0,
+                regen_delay: 2.0,
+                broken_lockout: 5.0,
+            },
+            warp_distance: 200.0,
+            warp_cooldown: 5.0,
+            weapon: Loadout {
+                projectile_speed: 400.0,
+                projectile_radius: 5.0,
+                projectile_damage: 100,
+                projectile_range: 900.0,
+                barrels: 1,
+                barrel_spacing: 0.0,
+                magazine: 8,
+                reload_time: 1.5,
+                auto_reload: true,
+            },
+            sprites: [asset_server.load("starred.png"), asset_server.load("starblue.png")],
+        }
+    }
+}
+
+/// How a ship's main gun fires
+#[derive(Component, Deserialize, Clone, Debug)]
+pub struct Loadout {
+    pub projectile_speed: f32,
+    pub projectile_radius: f32,
+    pub projectile_damage: i32,
+    /// Distance a shot travels before fizzling out
+    pub projectile_range: f32,
+    /// Parallel shots per trigger pull
+    pub barrels: u32,
+    /// Gap between neighbouring barrels
+    pub barrel_spacing: f32,
+    /// Trigger pulls before a reload, however many barrels fire
+    pub magazine: u32,
+    /// Seconds
+    pub reload_time: f32,
+    pub auto_reload: bool,
+}
+
+/// How a ship's shield drains and recovers
+#[derive(Component, Deserialize, Clone, Debug)]
+pub struct ShieldStats {
+    /// Points lost per second while it is held up
+    pub drain: f32,
+    /// Points regained per second once it has been left alone
+    pub regen: f32,
+    /// Seconds after the shield was last up before it starts to regain points
+    pub regen_delay: f32,
+    /// Seconds it can't be raised or regain points after being knocked down to nothing
+    pub broken_lockout: f32,
+}
+
+/// On-disk layout of a `ShipDef`, with sprites still as paths
+#[derive(Deserialize)]
+struct ShipDefFile {
+    name: String,
+    movement_speed: f32,
+    /// Degrees per second, friendlier to edit by hand
+    rotation_speed: f32,
+    radius: f32,
+    max_health: i32,
+    #[serde(default)]
+    armor: i32,
+    max_shield: f32,
+    shield: ShieldStats,
+    warp_distance: f32,
+    warp_cooldown: f32,
+    weapon: Loadout,
+    sprites: [String; 2],
+}
+
+#[derive(Debug, Error)]
+pub enum ShipDefLoaderError {
+    #[error("could not read ship file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse ship file: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+#[derive(Default)]
+struct ShipDefLoader;
+
+impl AssetLoader for ShipDefLoader {
+    type Asset = ShipDef;
+    type Settings = ();
+    type Error = ShipDefLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<ShipDef, ShipDefLoaderError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let file: ShipDefFile = ron::de::from_bytes(&bytes)?;
+        let [red, blue] = file.sprites;
+
+        Ok(ShipDef {
+            name: file.name,
+            movement_speed: file.movement_speed,
+            rotation_speed: file.rotation_speed.to_radians(),
+            radius: file.radius,
+            max_health: file.max_health,
+            armor: file.armor,
+            max_shield: file.max_shield,
+            shield: file.shield,
+            warp_distance: file.warp_distance,
+            warp_cooldown: file.warp_cooldown,
+            weapon: file.weapon,
+            sprites: [load_context.load(red), load_context.load(blue)],
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ship.ron"]
+    }
+}
+
+/// Every ship archetype found in `assets/ships`, in file name order
+#[derive(Resource)]
+pub struct ShipRoster {
+    folder: Handle<LoadedFolder>,
+    ships: Vec<Handle<ShipDef>>,
+}
+
+impl ShipRoster {
+    pub fn get<'a>(&self, index: usize, defs: &'a Assets<ShipDef>) -> Option<&'a ShipDef> {
+        defs.get(self.ships.get(index)?)
+    }
+}
+
+/// Which archetype each player is flying this match
+#[derive(Resource, Default)]
+pub struct ShipChoices {
+    pub picks: [usize; 2],
+    ready: [bool; 2],
+}
+
+#[derive(Component)]
+struct ShipSelectText;
+
+fn load_ship_roster(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ShipRoster {
+        folder: asset_server.load_folder("ships"),
+        ships: Vec::new(),
+    });
+}
+
+/// Fills the roster once the ships folder and everything in it has loaded, or
+/// with the fallback ship if it never will
+fn fill_ship_roster(
+    mut events: MessageReader<AssetEvent<LoadedFolder>>,
+    mut failures: MessageReader<AssetLoadFailedEvent<LoadedFolder>>,
+    mut roster: ResMut<ShipRoster>,
+    folders: Res<Assets<LoadedFolder>>,
+    mut defs: ResMut<Assets<ShipDef>>,
+    asset_server: Res<AssetServer>,
+) {
+    let loaded = events.read().any(|event| event.is_loaded_with_dependencies(&roster.folder));
+    let failed = failures.read().any(|failure| failure.id == roster.folder.id());
+    if !loaded && !failed {
+        return;
+    }
+    let mut ships: Vec<Handle<ShipDef>> = folders
+        .get(&roster.folder)
+        .into_iter()
+        .flat_map(|folder| &folder.handles)
+        .filter_map(|handle| handle.clone().try_typed().ok())
+        .filter(|handle| defs.contains(handle))
+        .collect();
+    ships.sort_by_key(|handle| handle.path().map(|path| path.to_string()));
+    if ships.is_empty() {
+        warn!("No ship definitions found in assets/ships, falling back to the interceptor");
+        ships.push(defs.add(ShipDef::fallback(&asset_server)));
+    }
+    roster.ships = ships;
+}
+
+fn spawn_ship_select(mut commands: Commands, mut choices: ResMut<ShipChoices>) {
+    choices.ready = [false; 2];
+    commands.spawn((
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(16.0),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.05, 0.85)),
+        DespawnOnExit(GameState::ShipSelect),
+        children![
+            (
+                Text::new("Choose Your Ship"),
+                TextFont { font_size: 64.0, ..default() },
+                TextColor(Color::WHITE),
+            ),
+            (
+                Text::default(),
+                TextFont { font_size: 24.0, ..default() },
+                TextColor(Color::WHITE),
+                ShipSelectText,
+            ),
+            (
+                Text::new("Rotate to change ship, Fire to lock in, Esc to go back"),
+                TextFont { font_size: 20.0, ..default() },
+                TextColor(Color::srgb(0.7, 0.7, 0.8)),
+            ),
+        ],
+    ));
+}
+
+fn ship_select_input(
+    actions: PlayerActions,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    roster: Res<ShipRoster>,
+    defs: Res<Assets<ShipDef>>,
+    mut choices: ResMut<ShipChoices>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard.just_pressed(KeyCode::Escape) {
+        next_state.set(GameState::MainMenu);
+        return;
+    }
+    let count = roster.ships.len();
+    if count == 0 {
+        return;
+    }
+
+    for id in PLAYERS {
+        let i = id.index();
+        if actions.just_pressed(id, |b| &b.fire) {
+            // Only lock in once the file has actually loaded
+            choices.ready[i] = !choices.ready[i] && roster.get(choices.picks[i], &defs).is_some();
+        }
+        if choices.ready[i] {
+            continue;
+        }
+        if actions.just_pressed(id, |b| &b.rotate_left) {
+            choices.picks[i] = (choices.picks[i] + count - 1) % count;
+        }
+        if actions.just_pressed(id, |b| &b.rotate_right) {
+            choices.picks[i] = (choices.picks[i] + 1) % count;
+        }
+        choices.picks[i] %= count;
+    }
+
+    if choices.ready.iter().all(|ready| *ready) {
+        next_state.set(GameState::Playing);
+    }
+}
+
+fn update_ship_select_text(
+    roster: Res<ShipRoster>,
+    defs: Res<Assets<ShipDef>>,
+    choices: Res<ShipChoices>,
+    score: Res<MatchScore>,
+    mut texts: Query<&mut Text, With<ShipSelectText>>,
+) {
+    let mut lines = Vec::new();
+    for id in PLAYERS {
+        let i = id.index();
+        let pick = match roster.get(choices.picks[i], &defs) {
+            Some(def) => format!(
+                "< {} >  speed {:.0}  turn {:.0}  hull {}  armor {}  shield {:.0}  guns {}x{}",
+                def.name,
+                def.movement_speed,
+                def.rotation_speed.to_degrees(),
+                def.max_health,
+                def.armor,
+                def.max_shield,
+                def.weapon.barrels,
+                def.weapon.magazine,
+            ),
+            None => "Loading...".to_string(),
+        };
+        let ready = if choices.ready[i] { "  READY" } else { "" };
+        lines.push(format!("{}: {pick}{ready}", score.name(id)));
+    }
+
+    for mut text in &mut texts {
+        text.0 = lines.join("\n\n");
+    }
+}
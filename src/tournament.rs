@@ -0,0 +1,767 @@
+use std::{fs, path::PathBuf};
+
+use bevy::{
+    input::{
+        keyboard::{Key, KeyboardInput},
+        ButtonState,
+    },
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{any_gamepad_just_pressed, save_ron, GameState, MatchScore, PlayerId};
+
+const MIN_PLAYERS: usize = 4;
+const MAX_PLAYERS: usize = 16;
+const MAX_NAME_LEN: usize = 12;
+
+pub struct TournamentPlugin;
+
+impl Plugin for TournamentPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::MainMenu), leave_tournament)
+            .add_systems(OnEnter(GameState::TournamentLobby), spawn_lobby)
+            .add_systems(OnEnter(GameState::Bracket), spawn_bracket_screen)
+            .add_systems(
+                OnEnter(GameState::MatchOver),
+                record_match_result.run_if(resource_exists::<Tournament>),
+            )
+            .add_systems(
+                Update,
+                (
+                    lobby_input.run_if(in_state(GameState::TournamentLobby)),
+                    update_lobby_text.run_if(resource_changed::<TournamentLobby>),
+                    bracket_input.run_if(in_state(GameState::Bracket)),
+                ),
+            );
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+enum BracketFormat {
+    SingleElimination,
+    DoubleElimination,
+}
+
+impl BracketFormat {
+    fn label(self) -> &'static str {
+        match self {
+            BracketFormat::SingleElimination => "Single Elimination",
+            BracketFormat::DoubleElimination => "Double Elimination",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+enum BracketSide {
+    Winners,
+    Losers,
+    GrandFinal,
+}
+
+/// One seat in a bracket match
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+enum Slot {
+    /// Waiting on the result of an earlier match
+    Pending,
+    Player(usize),
+    /// Nobody will ever fill this seat, e.g. the other side of a bye
+    Empty,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct BracketMatch {
+    side: BracketSide,
+    round: u32,
+    slots: [Slot; 2],
+    /// `Some` once the match is settled, `Some(Slot::Empty)` if it was two byes
+    winner: Option<Slot>,
+    /// (match index, slot) the winner moves on to
+    winner_to: Option<(usize, usize)>,
+    /// (match index, slot) the loser drops to in double elimination
+    loser_to: Option<(usize, usize)>,
+}
+
+impl BracketMatch {
+    fn is_ready(&self) -> bool {
+        self.winner.is_none() && self.slots.iter().all(|slot| matches!(slot, Slot::Player(_)))
+    }
+}
+
+/// Full bracket state, saved after every result so a crashed tournament can be resumed
+#[derive(Resource, Serialize, Deserialize, Clone, Debug)]
+pub struct Tournament {
+    format: BracketFormat,
+    players: Vec<String>,
+    matches: Vec<BracketMatch>,
+    current: Option<usize>,
+    champion: Option<usize>,
+}
+
+impl Tournament {
+    fn new(format: BracketFormat, players: Vec<String>) -> Self {
+        let size = players.len().next_power_of_two();
+        let rounds = size.trailing_zeros();
+        let mut tournament = Self {
+            format,
+            players,
+            matches: Vec::new(),
+            current: None,
+            champion: None,
+        };
+
+        // Winners bracket, round r has size / 2^r matches
+        let mut winners: Vec<Vec<usize>> = Vec::new();
+        for round in 1..=rounds {
+            let ids = (0..size >> round)
+                .map(|_| tournament.push_match(BracketSide::Winners, round))
+                .collect::<Vec<_>>();
+            if let Some(previous) = winners.last() {
+                for (i, &id) in previous.iter().enumerate() {
+                    tournament.matches[id].winner_to = Some((ids[i / 2], i % 2));
+                }
+            }
+            winners.push(ids);
+        }
+
+        // Seed the first round so the top seeds only meet late and get the byes
+        let seeds = seed_order(size);
+        for (i, &id) in winners[0].iter().enumerate() {
+            for slot in 0..2 {
+                let seed = seeds[i * 2 + slot];
+                tournament.matches[id].slots[slot] = if seed < tournament.players.len() {
+                    Slot::Player(seed)
+                } else {
+                    Slot::Empty
+                };
+            }
+        }
+
+        if format == BracketFormat::DoubleElimination {
+            tournament.build_losers_bracket(&winners, size);
+        }
+
+        tournament.resolve_byes();
+        tournament
+    }
+
+    /// Losers bracket alternates between rounds that take in players knocked out of
+    /// the winners bracket and rounds that halve the field, ending in a grand final
+    fn build_losers_bracket(&mut self, winners: &[Vec<usize>], size: usize) {
+        let wb_rounds = winners.len();
+        let mut previous: Vec<usize> = Vec::new();
+
+        for round in 1..=2 * (wb_rounds as u32 - 1) {
+            let count = if round == 1 {
+                size / 4
+            } else if round % 2 == 0 {
+                size >> (round / 2 + 1)
+            } else {
+                size >> (round / 2 + 2)
+            };
+            let ids = (0..count)
+                .map(|_| self.push_match(BracketSide::Losers, round))
+                .collect::<Vec<_>>();
+
+            if round == 1 {
+                for (i, &wb) in winners[0].iter().enumerate() {
+                    self.matches[wb].loser_to = Some((ids[i / 2], i % 2));
+                }
+            } else if round % 2 == 0 {
+                // Survivors meet the players who just dropped from the winners bracket
+                let dropping = &winners[round as usize / 2];
+                for (i, &id) in ids.iter().enumerate() {
+                    self.matches[previous[i]].winner_to = Some((id, 0));
+                    self.matches[dropping[i]].loser_to = Some((id, 1));
+                }
+            } else {
+                for (i, &lb) in previous.iter().enumerate() {
+                    self.matches[lb].winner_to = Some((ids[i / 2], i % 2));
+                }
+            }
+            previous = ids;
+        }
+
+        let grand_final = self.push_match(BracketSide::GrandFinal, 1);
+        let wb_final = winners[wb_rounds - 1][0];
+        self.matches[wb_final].winner_to = Some((grand_final, 0));
+        self.matches[previous[0]].winner_to = Some((grand_final, 1));
+    }
+
+    fn push_match(&mut self, side: BracketSide, round: u32) -> usize {
+        self.matches.push(BracketMatch {
+            side,
+            round,
+            slots: [Slot::Pending, Slot::Pending],
+            winner: None,
+            winner_to: None,
+            loser_to: None,
+        });
+        self.matches.len() - 1
+    }
+
+    fn settle(&mut self, id: usize, winner: Slot, loser: Slot) {
+        let bracket_match = &mut self.matches[id];
+        bracket_match.winner = Some(winner);
+        let (winner_to, loser_to) = (bracket_match.winner_to, bracket_match.loser_to);
+
+        // The losers bracket champion has to beat the unbeaten player twice
+        let is_first_final = bracket_match.side == BracketSide::GrandFinal && bracket_match.round == 1;
+        if is_first_final && winner == bracket_match.slots[1] {
+            let reset = self.push_match(BracketSide::GrandFinal, 2);
+            self.matches[reset].slots = [loser, winner];
+            return;
+        }
+
+        match winner_to {
+            Some((next, slot)) => self.matches[next].slots[slot] = winner,
+            None => {
+                if let Slot::Player(player) = winner {
+                    self.champion = Some(player);
+                }
+            }
+        }
+        if let Some((next, slot)) = loser_to {
+            self.matches[next].slots[slot] = loser;
+        }
+    }
+
+    /// Walks byes forward until every unsettled match is waiting on a real game
+    fn resolve_byes(&mut self) {
+        loop {
+            let walkover = self.matches.iter().position(|m| {
+                m.winner.is_none()
+                    && !m.slots.contains(&Slot::Pending)
+                    && m.slots.contains(&Slot::Empty)
+            });
+            let Some(id) = walkover else {
+                break;
+            };
+            let advancing = self.matches[id]
+                .slots
+                .into_iter()
+                .find(|slot| matches!(slot, Slot::Player(_)))
+                .unwrap_or(Slot::Empty);
+            self.settle(id, advancing, Slot::Empty);
+        }
+    }
+
+    fn next_match(&self) -> Option<usize> {
+        self.matches
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.is_ready())
+            .min_by_key(|(_, m)| m.round)
+            .map(|(id, _)| id)
+    }
+
+    fn current_players(&self) -> Option<[usize; 2]> {
+        let [Slot::Player(red), Slot::Player(blue)] = self.matches[self.current?].slots else {
+            return None;
+        };
+        Some([red, blue])
+    }
+
+    fn record_winner(&mut self, winner: PlayerId) {
+        let Some(id) = self.current.take() else {
+            return;
+        };
+        let [red, blue] = self.matches[id].slots;
+        let (winner, loser) = match winner {
+            PlayerId::One => (red, blue),
+            PlayerId::Two => (blue, red),
+        };
+        self.settle(id, winner, loser);
+        self.resolve_byes();
+    }
+
+    fn slot_name(&self, slot: Slot) -> &str {
+        match slot {
+            Slot::Player(player) => &self.players[player],
+            Slot::Pending => "TBD",
+            Slot::Empty => "bye",
+        }
+    }
+
+    fn save_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("kuiper_belt").join("tournament.ron"))
+    }
+
+    pub fn has_save() -> bool {
+        Self::save_path().is_some_and(|path| path.exists())
+    }
+
+    pub fn load() -> Option<Self> {
+        let path = Self::save_path()?;
+        let contents = fs::read_to_string(&path).ok()?;
+        match ron::from_str(&contents) {
+            Ok(tournament) => Some(tournament),
+            Err(err) => {
+                warn!("Could not read saved tournament {}: {err}", path.display());
+                None
+            }
+        }
+    }
+
+    fn save(&self) {
+        let Some(path) = Self::save_path() else {
+            return;
+        };
+        save_ron(&path, "tournament", self);
+    }
+
+    fn clear_save() {
+        if let Some(path) = Self::save_path() {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Standard bracket seeding, 1 plays 8, 4 plays 5 and so on
+fn seed_order(size: usize) -> Vec<usize> {
+    let mut seeds = vec![0];
+    while seeds.len() < size {
+        let n = seeds.len() * 2;
+        seeds = seeds.iter().flat_map(|&seed| [seed, n - 1 - seed]).collect();
+    }
+    seeds
+}
+
+#[derive(Resource)]
+struct TournamentLobby {
+    names: Vec<String>,
+    input: String,
+    format: BracketFormat,
+}
+
+#[derive(Component)]
+struct LobbyText;
+
+fn leave_tournament(mut commands: Commands) {
+    commands.remove_resource::<Tournament>();
+}
+
+fn spawn_lobby(mut commands: Commands) {
+    commands.insert_resource(TournamentLobby {
+        names: Vec::new(),
+        input: String::new(),
+        format: BracketFormat::SingleElimination,
+    });
+    commands.spawn((
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.05, 0.85)),
+        DespawnOnExit(GameState::TournamentLobby),
+        children![(
+            Text::default(),
+            TextFont { font_size: 24.0, ..default() },
+            TextColor(Color::WHITE),
+            LobbyText,
+        )],
+    ));
+}
+
+fn lobby_input(
+    mut keys: MessageReader<KeyboardInput>,
+    mut lobby: ResMut<TournamentLobby>,
+    mut commands: Commands,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for key in keys.read() {
+        if key.state != ButtonState::Pressed {
+            continue;
+        }
+        match &key.logical_key {
+            Key::Enter if lobby.input.trim().is_empty() => {
+                if lobby.names.len() >= MIN_PLAYERS {
+                    let tournament = Tournament::new(lobby.format, lobby.names.clone());
+                    tournament.save();
+                    commands.insert_resource(tournament);
+                    next_state.set(GameState::Bracket);
+                }
+            }
+            Key::Enter => {
+                if lobby.names.len() < MAX_PLAYERS {
+                    let name = lobby.input.trim().to_string();
+                    lobby.names.push(name);
+                }
+                lobby.input.clear();
+            }
+            Key::Backspace => {
+                if lobby.input.pop().is_none() {
+                    lobby.names.pop();
+                }
+            }
+            Key::Tab => {
+                lobby.format = match lobby.format {
+                    BracketFormat::SingleElimination => BracketFormat::DoubleElimination,
+                    BracketFormat::DoubleElimination => BracketFormat::SingleElimination,
+                };
+            }
+            Key::Escape => next_state.set(GameState::MainMenu),
+            _ => {
+                if let Some(text) = &key.text {
+                    for c in text.chars().filter(|c| !c.is_control()) {
+                        if lobby.input.chars().count() < MAX_NAME_LEN {
+                            lobby.input.push(c);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn update_lobby_text(lobby: Res<TournamentLobby>, mut texts: Query<&mut Text, With<LobbyText>>) {
+    let mut lines = vec![
+        "Tournament".to_string(),
+        format!("Format: {} (Tab to change)", lobby.format.label()),
+        String::new(),
+    ];
+    for (i, name) in lobby.names.iter().enumerate() {
+        lines.push(format!("{:>2}. {name}", i + 1));
+    }
+    lines.push(format!("> {}_", lobby.input));
+    lines.push(String::new());
+    lines.push(format!(
+        "Type a name and press Enter to add ({}-{} players), Backspace to remove",
+        MIN_PLAYERS, MAX_PLAYERS
+    ));
+    if lobby.names.len() >= MIN_PLAYERS {
+        lines.push("Press Enter on an empty line to start".to_string());
+    }
+    lines.push("Esc to go back".to_string());
+
+    for mut text in &mut texts {
+        text.0 = lines.join("\n");
+    }
+}
+
+fn spawn_bracket_screen(mut commands: Commands, mut tournament: ResMut<Tournament>) {
+    if tournament.current.is_none() {
+        tournament.current = tournament.next_match();
+    }
+
+    let footer = if let Some(champion) = tournament.champion {
+        format!("{} is the champion! Enter / Start to finish", tournament.players[champion])
+    } else if let Some([red, blue]) = tournament.current_players() {
+        format!(
+            "Next: {} (Red) vs {} (Blue) - Enter / Start to play, Esc to save and quit",
+            tournament.players[red], tournament.players[blue]
+        )
+    } else {
+        "Waiting for results".to_string()
+    };
+
+    let sides = [
+        (BracketSide::Winners, "Winners"),
+        (BracketSide::Losers, "Losers"),
+        (BracketSide::GrandFinal, "Grand Final"),
+    ];
+
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(12.0),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.05, 0.9)),
+            DespawnOnExit(GameState::Bracket),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(format!("Tournament - {}", tournament.format.label())),
+                TextFont { font_size: 40.0, ..default() },
+                TextColor(Color::WHITE),
+            ));
+            for (side, label) in sides {
+                let rounds = tournament
+                    .matches
+                    .iter()
+                    .filter(|m| m.side == side)
+                    .map(|m| m.round)
+                    .max();
+                let Some(rounds) = rounds else {
+                    continue;
+                };
+                parent.spawn((
+                    Text::new(label),
+                    TextFont { font_size: 20.0, ..default() },
+                    TextColor(Color::srgb(0.7, 0.7, 0.8)),
+                ));
+                parent
+                    .spawn(Node {
+                        flex_direction: FlexDirection::Row,
+                        column_gap: Val::Px(24.0),
+                        align_items: AlignItems::Center,
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        for round in 1..=rounds {
+                            row.spawn(Node {
+                                flex_direction: FlexDirection::Column,
+                                row_gap: Val::Px(6.0),
+                                ..default()
+                            })
+                            .with_children(|column| {
+                                for (id, bracket_match) in tournament.matches.iter().enumerate() {
+                                    if bracket_match.side != side || bracket_match.round != round {
+                                        continue;
+                                    }
+                                    spawn_match_box(column, &tournament, id, bracket_match);
+                                }
+                            });
+                        }
+                    });
+            }
+            parent.spawn((
+                Text::new(footer),
+                TextFont { font_size: 22.0, ..default() },
+                TextColor(Color::srgb(1.0, 0.9, 0.3)),
+            ));
+        });
+}
+
+fn spawn_match_box(
+    parent: &mut ChildSpawnerCommands,
+    tournament: &Tournament,
+    id: usize,
+    bracket_match: &BracketMatch,
+) {
+    let lines = bracket_match
+        .slots
+        .map(|slot| {
+            let marker = if bracket_match.winner == Some(slot) { "> " } else { "  " };
+            format!("{marker}{}", tournament.slot_name(slot))
+        })
+        .join("\n");
+    let color = if tournament.current == Some(id) {
+        Color::srgb(1.0, 0.9, 0.3)
+    } else if bracket_match.winner.is_some() {
+        Color::srgb(0.5, 0.5, 0.55)
+    } else {
+        Color::WHITE
+    };
+    parent.spawn((
+        Node {
+            padding: UiRect::all(Val::Px(4.0)),
+            ..default()
+        },
+        BackgroundColor(Color::srgb(0.12, 0.12, 0.18)),
+        children![(
+            Text::new(lines),
+            TextFont { font_size: 14.0, ..default() },
+            TextColor(color),
+        )],
+    ));
+}
+
+fn bracket_input(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    tournament: Res<Tournament>,
+    mut score: ResMut<MatchScore>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard.just_pressed(KeyCode::Escape) {
+        next_state.set(GameState::MainMenu);
+        return;
+    }
+    if !keyboard.just_pressed(KeyCode::Enter) && !any_gamepad_just_pressed(&gamepads, GamepadButton::Start) {
+        return;
+    }
+
+    if tournament.champion.is_some() {
+        Tournament::clear_save();
+        next_state.set(GameState::MainMenu);
+    } else if let Some([red, blue]) = tournament.current_players() {
+        score.names = [tournament.players[red].clone(), tournament.players[blue].clone()];
+        // Through the join screen, so whoever is up can take a slot with their pad
+        next_state.set(GameState::PlayerSelect);
+    }
+}
+
+fn record_match_result(mut tournament: ResMut<Tournament>, score: Res<MatchScore>) {
+    let Some(winner) = score.match_winner else {
+        return;
+    };
+    tournament.record_winner(winner);
+    tournament.save();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENTRANTS: [usize; 5] = [3, 4, 5, 8, 16];
+    const FORMATS: [BracketFormat; 2] = [BracketFormat::SingleElimination, BracketFormat::DoubleElimination];
+
+    fn tournament(format: BracketFormat, entrants: usize) -> Tournament {
+        Tournament::new(format, (0..entrants).map(|i| format!("P{i}")).collect())
+    }
+
+    /// Plays the next ready match, gives false once there is nothing left to play
+    fn play_next(tournament: &mut Tournament, winner: PlayerId) -> bool {
+        tournament.current = tournament.next_match();
+        if tournament.current.is_none() {
+            return false;
+        }
+        tournament.record_winner(winner);
+        true
+    }
+
+    fn side_count(tournament: &Tournament, side: BracketSide) -> usize {
+        tournament.matches.iter().filter(|m| m.side == side).count()
+    }
+
+    #[test]
+    fn seed_order_pairs_top_and_bottom_seeds() {
+        assert_eq!(seed_order(4), [0, 3, 1, 2]);
+        assert_eq!(seed_order(8), [0, 7, 3, 4, 1, 6, 2, 5]);
+        for size in [2, 4, 8, 16] {
+            let seeds = seed_order(size);
+            for pair in seeds.chunks(2) {
+                assert_eq!(pair[0] + pair[1], size - 1);
+            }
+        }
+    }
+
+    #[test]
+    fn bracket_sizes() {
+        for entrants in ENTRANTS {
+            let size = entrants.next_power_of_two();
+
+            let single = tournament(BracketFormat::SingleElimination, entrants);
+            assert_eq!(single.matches.len(), size - 1, "{entrants} entrants");
+            assert_eq!(side_count(&single, BracketSide::Losers), 0);
+            assert_eq!(side_count(&single, BracketSide::GrandFinal), 0);
+
+            // n - 1 winners matches, n - 2 losers matches and a grand final
+            let double = tournament(BracketFormat::DoubleElimination, entrants);
+            assert_eq!(double.matches.len(), 2 * size - 2, "{entrants} entrants");
+            assert_eq!(side_count(&double, BracketSide::Winners), size - 1);
+            assert_eq!(side_count(&double, BracketSide::Losers), size - 2);
+            assert_eq!(side_count(&double, BracketSide::GrandFinal), 1);
+        }
+    }
+
+    #[test]
+    fn byes_advance_top_seeds() {
+        for format in FORMATS {
+            for entrants in ENTRANTS {
+                let tournament = tournament(format, entrants);
+                let byes = entrants.next_power_of_two() - entrants;
+                let second_round: Vec<Slot> = tournament
+                    .matches
+                    .iter()
+                    .filter(|m| m.side == BracketSide::Winners && m.round == 2)
+                    .flat_map(|m| m.slots)
+                    .collect();
+                for seed in 0..entrants {
+                    assert_eq!(
+                        second_round.contains(&Slot::Player(seed)),
+                        seed < byes,
+                        "seed {seed} of {entrants}"
+                    );
+                }
+                // Every bye that can be walked over already has been
+                assert!(!tournament.matches.iter().any(|m| {
+                    m.winner.is_none() && !m.slots.contains(&Slot::Pending) && m.slots.contains(&Slot::Empty)
+                }));
+            }
+        }
+    }
+
+    #[test]
+    fn every_bracket_plays_out_to_a_champion() {
+        for format in FORMATS {
+            for entrants in ENTRANTS {
+                let mut tournament = tournament(format, entrants);
+                let mut played = 0;
+                while play_next(&mut tournament, PlayerId::One) {
+                    played += 1;
+                    assert!(played <= tournament.matches.len(), "{entrants} entrants never finished");
+                }
+                assert!(tournament.champion.is_some(), "{format:?} with {entrants} entrants");
+                // The top seed wins every game it plays
+                assert_eq!(tournament.champion, Some(0));
+            }
+        }
+    }
+
+    #[test]
+    fn losers_bracket_winner_forces_a_reset() {
+        for entrants in ENTRANTS {
+            let mut tournament = tournament(BracketFormat::DoubleElimination, entrants);
+            let grand_final = tournament
+                .matches
+                .iter()
+                .position(|m| m.side == BracketSide::GrandFinal)
+                .unwrap();
+            while tournament.next_match() != Some(grand_final) {
+                assert!(play_next(&mut tournament, PlayerId::One));
+            }
+            let [Slot::Player(unbeaten), Slot::Player(challenger)] = tournament.matches[grand_final].slots else {
+                panic!("grand final is missing a player");
+            };
+
+            assert!(play_next(&mut tournament, PlayerId::Two));
+            assert_eq!(tournament.champion, None);
+            let reset = tournament.next_match().expect("no reset match");
+            assert_eq!(tournament.matches[reset].side, BracketSide::GrandFinal);
+            assert_eq!(tournament.matches[reset].round, 2);
+            assert_eq!(tournament.matches[reset].slots, [Slot::Player(unbeaten), Slot::Player(challenger)]);
+
+            assert!(play_next(&mut tournament, PlayerId::Two));
+            assert_eq!(tournament.champion, Some(challenger));
+            assert!(!play_next(&mut tournament, PlayerId::One));
+        }
+    }
+
+    #[test]
+    fn unbeaten_player_wins_the_grand_final_outright() {
+        let mut tournament = tournament(BracketFormat::DoubleElimination, 8);
+        while play_next(&mut tournament, PlayerId::One) {}
+        assert_eq!(side_count(&tournament, BracketSide::GrandFinal), 1);
+        assert_eq!(tournament.champion, Some(0));
+    }
+
+    #[test]
+    fn save_round_trip() {
+        for format in FORMATS {
+            let mut tournament = tournament(format, 5);
+            play_next(&mut tournament, PlayerId::Two);
+            // Saved with the next match picked, as the bracket screen does
+            tournament.current = tournament.next_match();
+
+            let saved = ron::ser::to_string_pretty(&tournament, ron::ser::PrettyConfig::default()).unwrap();
+            let mut loaded: Tournament = ron::from_str(&saved).unwrap();
+
+            assert_eq!(loaded.format, tournament.format);
+            assert_eq!(loaded.players, tournament.players);
+            assert_eq!(loaded.current, tournament.current);
+            assert_eq!(loaded.champion, tournament.champion);
+            assert_eq!(loaded.matches.len(), tournament.matches.len());
+            for (a, b) in loaded.matches.iter().zip(&tournament.matches) {
+                assert_eq!(
+                    (a.side, a.round, a.slots, a.winner, a.winner_to, a.loser_to),
+                    (b.side, b.round, b.slots, b.winner, b.winner_to, b.loser_to)
+                );
+            }
+
+            // Picks up where it left off
+            while play_next(&mut loaded, PlayerId::One) {}
+            assert!(loaded.champion.is_some());
+        }
+    }
+}
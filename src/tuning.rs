@@ -0,0 +1,219 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    prelude::*,
+};
+use serde::Deserialize;
+use thiserror::Error;
+
+/// Balance values live in this file so they can be changed while the game runs.
+/// Per-ship stats (speed, warp, guns, shields) are in `assets/ships` instead.
+const TUNING_PATH: &str = "game.tuning.ron";
+/// Shortest gap between asteroid spawns a tuning file can ask for
+const MIN_ASTEROID_INTERVAL: f32 = 0.1;
+
+pub struct TuningPlugin;
+
+impl Plugin for TuningPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<GameTuning>()
+            .init_asset_loader::<GameTuningLoader>()
+            .init_resource::<GameTuning>()
+            .add_systems(Startup, load_tuning)
+            .add_systems(PreUpdate, apply_tuning);
+    }
+}
+
+/// Gameplay numbers shared by every system, copied into a resource whenever
+/// the file is (re)loaded
+#[derive(Asset, Resource, TypePath, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct GameTuning {
+    /// Fraction of velocity shed per second
+    pub friction: f32,
+    /// Hull lost per unit of closing speed when a ship hits another ship or an asteroid
+    pub impact_damage: f32,
+    /// Closing speed below which bumps do no damage
+    pub impact_safe_speed: f32,
+    /// Seconds a ship shrugs off further impacts after taking a hit
+    pub impact_invulnerability: f32,
+    pub shield_radius: f32,
+    /// Shield spent for every point of damage it soaks up
+    pub shield_cost: f32,
+    /// Seconds after raising the shield during which incoming shots are reflected
+    pub parry_window: f32,
+    /// Seconds after a parry window closes before raising the shield can open another
+    pub parry_cooldown: f32,
+    /// Shield restored by picking up a nickel
+    pub nickel_shield: f32,
+    pub nickel_radius: f32,
+    /// Seconds between asteroid spawns
+    pub asteroid_interval: f32,
+    /// Nominal radius of small, medium and large asteroids
+    pub asteroid_radii: (f32, f32, f32),
+    pub asteroid_speed: (f32, f32),
+    /// Radians either side of the centre an incoming asteroid can aim
+    pub asteroid_approach_spread: f32,
+    pub fragment_speed: (f32, f32),
+    /// Seconds the round over banner stays up
+    pub round_over_delay: f32,
+    /// Seconds before a shot that hits nothing is removed
+    pub projectile_lifetime: f32,
+    /// Chance that a small asteroid drops a weapon instead of a nickel
+    pub weapon_drop_chance: f32,
+    pub spread: WeaponStats,
+    pub beam: WeaponStats,
+    pub homing: WeaponStats,
+    pub mine: WeaponStats,
+}
+
+/// Balance for one of the weapons picked up from asteroid drops
+#[derive(Deserialize, Clone, Debug)]
+pub struct WeaponStats {
+    /// Per projectile, or per blast for mines
+    pub damage: i32,
+    pub speed: f32,
+    /// Seconds between shots
+    pub cooldown: f32,
+    /// Shots before the ship falls back to its laser
+    pub uses: u32,
+}
+
+impl Default for GameTuning {
+    fn default() -> Self {
+        Self {
+            friction: 0.8,
+            impact_damage: 0.4,
+            impact_safe_speed: 80.0,
+            impact_invulnerability: 0.75,
+            shield_radius: 40.0,
+            shield_cost: 1.0,
+            parry_window: 0.15,
+            parry_cooldown: 1.0,
+            nickel_shield: 100.0,
+            nickel_radius: 7.0,
+            asteroid_interval: 5.0,
+            asteroid_radii: (22.0, 40.0, 70.0),
+            asteroid_speed: (20.0, 70.0),
+            asteroid_approach_spread: 0.5,
+            fragment_speed: (60.0, 160.0),
+            round_over_delay: 1.5,
+            projectile_lifetime: 3.0,
+            weapon_drop_chance: 0.3,
+            spread: WeaponStats { damage: 50, speed: 380.0, cooldown: 0.6, uses: 6 },
+            beam: WeaponStats { damage: 300, speed: 1200.0, cooldown: 1.0, uses: 3 },
+            homing: WeaponStats { damage: 150, speed: 260.0, cooldown: 1.2, uses: 4 },
+            mine: WeaponStats { damage: 200, speed: 0.0, cooldown: 0.8, uses: 3 },
+        }
+    }
+}
+
+impl GameTuning {
+    /// Pulls hand edited values back into ranges the game can run with, so a
+    /// bad edit mid session logs a warning instead of crashing
+    fn sanitize(&mut self) {
+        let defaults = Self::default();
+        let clamped = |name: &str, value: &mut f32, min: f32, max: f32, default: f32| {
+            let fixed = if value.is_finite() { value.clamp(min, max) } else { default };
+            if fixed != *value {
+                warn!("Tuning {name} of {value} is out of range, using {fixed}");
+                *value = fixed;
+            }
+        };
+        let at_least = |name: &str, value: &mut f32, min: f32, default: f32| {
+            clamped(name, value, min, f32::INFINITY, default);
+        };
+        let range = |name: &str, value: &mut (f32, f32), default: (f32, f32)| {
+            let fixed = if value.0.is_finite() && value.1.is_finite() {
+                (value.0.min(value.1), value.0.max(value.1))
+            } else {
+                default
+            };
+            if fixed != *value {
+                warn!("Tuning {name} of {value:?} is out of range, using {fixed:?}");
+                *value = fixed;
+            }
+        };
+
+        at_least("impact_invulnerability", &mut self.impact_invulnerability, 0.0, defaults.impact_invulnerability);
+        at_least("parry_window", &mut self.parry_window, 0.0, defaults.parry_window);
+        at_least("parry_cooldown", &mut self.parry_cooldown, 0.0, defaults.parry_cooldown);
+        at_least("asteroid_interval", &mut self.asteroid_interval, MIN_ASTEROID_INTERVAL, defaults.asteroid_interval);
+        at_least(
+            "asteroid_approach_spread",
+            &mut self.asteroid_approach_spread,
+            0.0,
+            defaults.asteroid_approach_spread,
+        );
+        at_least("round_over_delay", &mut self.round_over_delay, 0.0, defaults.round_over_delay);
+        at_least("projectile_lifetime", &mut self.projectile_lifetime, 0.0, defaults.projectile_lifetime);
+        clamped("weapon_drop_chance", &mut self.weapon_drop_chance, 0.0, 1.0, defaults.weapon_drop_chance);
+        range("asteroid_speed", &mut self.asteroid_speed, defaults.asteroid_speed);
+        range("fragment_speed", &mut self.fragment_speed, defaults.fragment_speed);
+        for (name, stats, default) in [
+            ("spread", &mut self.spread, &defaults.spread),
+            ("beam", &mut self.beam, &defaults.beam),
+            ("homing", &mut self.homing, &defaults.homing),
+            ("mine", &mut self.mine, &defaults.mine),
+        ] {
+            at_least(&format!("{name}.cooldown"), &mut stats.cooldown, 0.0, default.cooldown);
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum TuningLoaderError {
+    #[error("could not read tuning file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse tuning file: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+#[derive(Default)]
+struct GameTuningLoader;
+
+impl AssetLoader for GameTuningLoader {
+    type Asset = GameTuning;
+    type Settings = ();
+    type Error = TuningLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<GameTuning, TuningLoaderError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tuning.ron"]
+    }
+}
+
+/// Keeps the tuning file loaded so edits get picked up
+#[derive(Resource)]
+struct TuningHandle(Handle<GameTuning>);
+
+fn load_tuning(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(TuningHandle(asset_server.load(TUNING_PATH)));
+}
+
+fn apply_tuning(
+    mut events: MessageReader<AssetEvent<GameTuning>>,
+    handle: Res<TuningHandle>,
+    assets: Res<Assets<GameTuning>>,
+    mut tuning: ResMut<GameTuning>,
+) {
+    for event in events.read() {
+        if !event.is_loaded_with_dependencies(&handle.0) && !event.is_modified(&handle.0) {
+            continue;
+        }
+        if let Some(loaded) = assets.get(&handle.0) {
+            *tuning = loaded.clone();
+            tuning.sanitize();
+            info!("Applied gameplay tuning from {TUNING_PATH}");
+        }
+    }
+}
@@ -0,0 +1,432 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use bevy_kira_audio::{Audio, AudioControl, AudioSource};
+use rand::Rng;
+
+use crate::{
+    arena::{Arena, Wraps},
+    damage::{DamageEvent, DamageKind},
+    physics::{BroadPhase, PhysicsSet},
+    projectile_color_for,
+    tuning::{GameTuning, WeaponStats},
+    GameEntity, MatchStats, Player, PlayerId, Projectile, ShipInput,
+};
+
+const SPREAD_SHOTS: u32 = 5;
+/// Angle between the outermost shots of a spread
+const SPREAD_ARC: f32 = PI / 4.0;
+/// Seconds of holding fire for a full strength beam
+const BEAM_CHARGE_TIME: f32 = 1.2;
+/// A tap still fires a beam at this fraction of full strength
+const BEAM_MIN_CHARGE: f32 = 0.3;
+/// End to end length of a beam, in multiples of its radius
+const BEAM_LENGTH: f32 = 8.0;
+/// Radians per second a missile can turn toward its target
+const HOMING_TURN_RATE: f32 = 2.5;
+const MINE_RADIUS: f32 = 8.0;
+const MINE_ARM_TIME: f32 = 1.0;
+const MINE_LIFETIME: f32 = 20.0;
+const MINE_TRIGGER_RADIUS: f32 = 60.0;
+const MINE_BLAST_RADIUS: f32 = 90.0;
+const PICKUP_RADIUS: f32 = 10.0;
+
+pub struct WeaponsPlugin;
+
+impl Plugin for WeaponsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, load_weapon_sounds).add_systems(
+            FixedUpdate,
+            (
+                (fire_special_weapon, steer_homing).in_set(PhysicsSet::Input),
+                (mine_system, ship_pickup_collision).in_set(PhysicsSet::Collide),
+            ),
+        );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WeaponKind {
+    Spread,
+    Beam,
+    Homing,
+    Mine,
+}
+
+impl WeaponKind {
+    const ALL: [WeaponKind; 4] = [WeaponKind::Spread, WeaponKind::Beam, WeaponKind::Homing, WeaponKind::Mine];
+
+    fn random(rng: &mut impl Rng) -> Self {
+        Self::ALL[rng.random_range(0..Self::ALL.len())]
+    }
+
+    /// Short enough to fit beside the health blocks
+    pub fn label(self) -> &'static str {
+        match self {
+            WeaponKind::Spread => "SPR",
+            WeaponKind::Beam => "BEAM",
+            WeaponKind::Homing => "MSL",
+            WeaponKind::Mine => "MINE",
+        }
+    }
+
+    fn stats(self, tuning: &GameTuning) -> &WeaponStats {
+        match self {
+            WeaponKind::Spread => &tuning.spread,
+            WeaponKind::Beam => &tuning.beam,
+            WeaponKind::Homing => &tuning.homing,
+            WeaponKind::Mine => &tuning.mine,
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            WeaponKind::Spread => Color::srgb(1.0, 0.8, 0.2),
+            WeaponKind::Beam => Color::srgb(0.6, 1.0, 1.0),
+            WeaponKind::Homing => Color::srgb(1.0, 0.5, 0.1),
+            WeaponKind::Mine => Color::srgb(0.8, 0.3, 1.0),
+        }
+    }
+}
+
+/// A picked up weapon, fired in place of the ship's laser until its uses run out
+#[derive(Component)]
+pub struct EquippedWeapon {
+    pub kind: WeaponKind,
+    pub uses: u32,
+    cooldown: Timer,
+    /// 0.0 to 1.0, only used by the beam
+    charge: f32,
+}
+
+impl EquippedWeapon {
+    fn new(kind: WeaponKind, tuning: &GameTuning) -> Self {
+        let stats = kind.stats(tuning);
+        let mut cooldown = Timer::from_seconds(stats.cooldown, TimerMode::Once);
+        // Ready to fire the moment it is picked up
+        cooldown.tick(cooldown.duration());
+        Self {
+            kind,
+            uses: stats.uses,
+            cooldown,
+            charge: 0.0,
+        }
+    }
+}
+
+/// Floating drop left behind by a destroyed asteroid
+#[derive(Component)]
+pub struct WeaponPickup {
+    kind: WeaponKind,
+    pub radius: f32,
+}
+
+#[derive(Component)]
+struct Homing;
+
+#[derive(Component)]
+struct Mine {
+    owner: PlayerId,
+    damage: i32,
+    arm: Timer,
+    lifetime: Timer,
+}
+
+#[derive(Resource)]
+struct WeaponSounds {
+    spread: Handle<AudioSource>,
+    beam: Handle<AudioSource>,
+    missile: Handle<AudioSource>,
+    mine: Handle<AudioSource>,
+}
+
+impl WeaponSounds {
+    fn get(&self, kind: WeaponKind) -> Handle<AudioSource> {
+        match kind {
+            WeaponKind::Spread => self.spread.clone(),
+            WeaponKind::Beam => self.beam.clone(),
+            WeaponKind::Homing => self.missile.clone(),
+            WeaponKind::Mine => self.mine.clone(),
+        }
+    }
+}
+
+fn load_weapon_sounds(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(WeaponSounds {
+        spread: asset_server.load("sounds/spread.wav"),
+        beam: asset_server.load("sounds/beam.wav"),
+        missile: asset_server.load("sounds/missile.wav"),
+        mine: asset_server.load("sounds/mine.wav"),
+    });
+}
+
+/// Drops a random weapon where an asteroid broke up
+pub fn spawn_pickup(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    position: Vec3,
+) {
+    let kind = WeaponKind::random(&mut rand::rng());
+    commands.spawn((
+        Mesh2d(meshes.add(Rectangle::new(PICKUP_RADIUS * 1.4, PICKUP_RADIUS * 1.4))),
+        MeshMaterial2d(materials.add(kind.color())),
+        // Stood on its corner so it reads differently from a round nickel
+        Transform::from_translation(position).with_rotation(Quat::from_rotation_z(PI / 4.0)),
+        WeaponPickup {
+            kind,
+            radius: PICKUP_RADIUS,
+        },
+        GameEntity,
+    ));
+}
+
+fn ship_pickup_collision(
+    mut commands: Commands,
+    tuning: Res<GameTuning>,
+    arena: Res<Arena>,
+    grid: Res<BroadPhase>,
+    ships: Query<(Entity, &Transform, &Player)>,
+    pickups: Query<(Entity, &Transform, &WeaponPickup)>,
+) {
+    for (ship_entity, ship_tf, ship) in &ships {
+        let ship_pos = ship_tf.translation.truncate();
+        for candidate in grid.near(ship_pos, ship.radius) {
+            let Ok((pickup_entity, pickup_tf, pickup)) = pickups.get(candidate) else {
+                continue;
+            };
+            if arena.distance(ship_pos, pickup_tf.translation.truncate()) < ship.radius + pickup.radius {
+                // A new pickup replaces whatever was equipped
+                commands
+                    .entity(ship_entity)
+                    .insert(EquippedWeapon::new(pickup.kind, &tuning));
+                commands.entity(pickup_entity).despawn();
+                break;
+            }
+        }
+    }
+}
+
+fn fire_special_weapon(
+    mut commands: Commands,
+    time: Res<Time>,
+    tuning: Res<GameTuning>,
+    audio: Res<Audio>,
+    sounds: Res<WeaponSounds>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut stats: ResMut<MatchStats>,
+    mut ships: Query<(Entity, &Transform, &PlayerId, &mut ShipInput, &mut EquippedWeapon)>,
+) {
+    for (entity, transform, id, mut input, mut weapon) in &mut ships {
+        weapon.cooldown.tick(time.delta());
+        let fire_pressed = std::mem::take(&mut input.fire);
+        if !weapon.cooldown.is_finished() {
+            continue;
+        }
+
+        let kind = weapon.kind;
+        let weapon_stats = kind.stats(&tuning);
+        let forward = (transform.rotation * Vec3::Y).truncate().normalize();
+        let origin = transform.translation;
+        let color = projectile_color_for(*id);
+
+        let shots = match kind {
+            WeaponKind::Spread => {
+                if !fire_pressed {
+                    continue;
+                }
+                let mesh = meshes.add(Circle::new(3.0));
+                let material = materials.add(color);
+                for i in 0..SPREAD_SHOTS {
+                    let angle = SPREAD_ARC * (i as f32 / (SPREAD_SHOTS - 1) as f32 - 0.5);
+                    commands.spawn((
+                        Mesh2d(mesh.clone()),
+                        MeshMaterial2d(material.clone()),
+                        Transform::from_translation(origin),
+                        shot(*id, Vec2::from_angle(angle).rotate(forward) * weapon_stats.speed, 4.0, weapon_stats.damage, &tuning),
+                        Wraps { radius: 4.0 },
+                        GameEntity,
+                    ));
+                }
+                SPREAD_SHOTS
+            }
+            WeaponKind::Beam => {
+                // Build charge while fire is held, let go to shoot
+                if input.fire_held {
+                    weapon.charge = (weapon.charge + time.delta_secs() / BEAM_CHARGE_TIME).min(1.0);
+                    continue;
+                }
+                if weapon.charge <= 0.0 {
+                    continue;
+                }
+                let charge = std::mem::take(&mut weapon.charge).max(BEAM_MIN_CHARGE);
+                let radius = 4.0 + 6.0 * charge;
+                let half_length = radius * (BEAM_LENGTH / 2.0 - 1.0);
+                commands.spawn((
+                    Mesh2d(meshes.add(Capsule2d::new(radius, radius * (BEAM_LENGTH - 2.0)))),
+                    MeshMaterial2d(materials.add(kind.color())),
+                    Transform::from_translation(origin).with_rotation(transform.rotation),
+                    // Hits along its whole length, not just round its middle
+                    Projectile {
+                        half_length,
+                        ..shot(
+                            *id,
+                            forward * weapon_stats.speed,
+                            radius,
+                            (weapon_stats.damage as f32 * charge).round() as i32,
+                            &tuning,
+                        )
+                    },
+                    Wraps { radius: half_length + radius },
+                    GameEntity,
+                ));
+                1
+            }
+            WeaponKind::Homing => {
+                if !fire_pressed {
+                    continue;
+                }
+                commands.spawn((
+                    Mesh2d(meshes.add(Triangle2d::new(
+                        Vec2::new(0.0, 8.0),
+                        Vec2::new(-4.0, -6.0),
+                        Vec2::new(4.0, -6.0),
+                    ))),
+                    MeshMaterial2d(materials.add(kind.color())),
+                    Transform::from_translation(origin).with_rotation(transform.rotation),
+                    shot(*id, forward * weapon_stats.speed, 6.0, weapon_stats.damage, &tuning),
+                    Homing,
+                    // Out to the nose of the dart
+                    Wraps { radius: 8.0 },
+                    GameEntity,
+                ));
+                1
+            }
+            WeaponKind::Mine => {
+                if !fire_pressed {
+                    continue;
+                }
+                // Dropped off the tail rather than the nose
+                commands.spawn((
+                    Mesh2d(meshes.add(Circle::new(MINE_RADIUS))),
+                    MeshMaterial2d(materials.add(kind.color())),
+                    Transform::from_translation(origin - (forward * 30.0).extend(0.0)),
+                    Mine {
+                        owner: *id,
+                        damage: weapon_stats.damage,
+                        arm: Timer::from_seconds(MINE_ARM_TIME, TimerMode::Once),
+                        lifetime: Timer::from_seconds(MINE_LIFETIME, TimerMode::Once),
+                    },
+                    Wraps { radius: MINE_RADIUS },
+                    GameEntity,
+                ));
+                1
+            }
+        };
+
+        audio.play(sounds.get(kind));
+        stats.players[id.index()].shots_fired += shots;
+        weapon.cooldown.reset();
+        weapon.uses = weapon.uses.saturating_sub(1);
+        if weapon.uses == 0 {
+            commands.entity(entity).remove::<EquippedWeapon>();
+        }
+    }
+}
+
+fn shot(owner: PlayerId, velocity: Vec2, radius: f32, damage: i32, tuning: &GameTuning) -> Projectile {
+    Projectile {
+        velocity,
+        radius,
+        owner,
+        damage,
+        lifetime: Timer::from_seconds(tuning.projectile_lifetime, TimerMode::Once),
+        range: f32::INFINITY,
+        sweep: Vec2::ZERO,
+        half_length: 0.0,
+    }
+}
+
+/// Turns missiles toward the closest enemy ship
+fn steer_homing(
+    time: Res<Time>,
+    arena: Res<Arena>,
+    mut missiles: Query<(&mut Transform, &mut Projectile), With<Homing>>,
+    ships: Query<(&Transform, &PlayerId), (With<Player>, Without<Homing>)>,
+) {
+    for (mut transform, mut projectile) in &mut missiles {
+        let pos = transform.translation.truncate();
+        // Offsets rather than positions, so a target across the seam is chased the short way
+        let to_target = ships
+            .iter()
+            .filter(|(_, id)| **id != projectile.owner)
+            .map(|(tf, _)| arena.delta(pos, tf.translation.truncate()))
+            .min_by(|a, b| a.length_squared().total_cmp(&b.length_squared()));
+        let Some(to_target) = to_target else {
+            continue;
+        };
+
+        let max_turn = HOMING_TURN_RATE * time.delta_secs();
+        let turn = projectile.velocity.angle_to(to_target).clamp(-max_turn, max_turn);
+        projectile.velocity = Vec2::from_angle(turn).rotate(projectile.velocity);
+        transform.rotation = Quat::from_rotation_arc_2d(Vec2::Y, projectile.velocity.normalize());
+    }
+}
+
+/// Arms mines after a moment and sets them off when an enemy ship gets close.
+/// They go off under the hull, so shields don't help.
+fn mine_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    arena: Res<Arena>,
+    audio: Res<Audio>,
+    sounds: Res<WeaponSounds>,
+    mut mines: Query<(Entity, &Transform, &mut Mine)>,
+    ships: Query<(Entity, &Transform, &PlayerId), With<Player>>,
+    mut damage: MessageWriter<DamageEvent>,
+    mut stats: ResMut<MatchStats>,
+    grid: Res<BroadPhase>,
+) {
+    for (mine_entity, mine_tf, mut mine) in &mut mines {
+        mine.arm.tick(time.delta());
+        mine.lifetime.tick(time.delta());
+        if mine.lifetime.is_finished() {
+            commands.entity(mine_entity).despawn();
+            continue;
+        }
+        if !mine.arm.is_finished() {
+            continue;
+        }
+
+        let mine_pos = mine_tf.translation.truncate();
+        let triggered = grid
+            .near(mine_pos, MINE_TRIGGER_RADIUS)
+            .into_iter()
+            .filter_map(|candidate| ships.get(candidate).ok())
+            .any(|(_, tf, id)| {
+                *id != mine.owner && arena.distance(tf.translation.truncate(), mine_pos) < MINE_TRIGGER_RADIUS
+            });
+        if !triggered {
+            continue;
+        }
+
+        for candidate in grid.near(mine_pos, MINE_BLAST_RADIUS) {
+            let Ok((ship_entity, tf, id)) = ships.get(candidate) else {
+                continue;
+            };
+            if *id == mine.owner || arena.distance(tf.translation.truncate(), mine_pos) > MINE_BLAST_RADIUS {
+                continue;
+            }
+            stats.players[mine.owner.index()].shots_hit += 1;
+            damage.write(DamageEvent {
+                target: ship_entity,
+                source: Some(mine.owner),
+                amount: mine.damage,
+                kind: DamageKind::Explosion,
+            });
+        }
+        audio.play(sounds.mine.clone());
+        commands.entity(mine_entity).despawn();
+    }
+}